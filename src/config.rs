@@ -0,0 +1,237 @@
+use std::{collections::HashMap, env, error, fs, io, path::PathBuf, str::FromStr};
+
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	pub keys: HashMap<String, KeyNames>, // action name to key names, e.g. scroll_down = ["j", "down"]
+	pub theme: ThemeConfig,
+	pub dim: DimConfig,
+	pub layouts: Vec<ColumnLayout>, // cycled through by `L`, instead of DEFAULT_LAYOUTS
+	pub abbrev: Option<usize>,      // hex digits of shas in the blame, instead of core.abbrev
+	pub page_scroll: Option<PageScroll>,
+	pub history_args: Vec<String>, // added to git log -L for the line history, e.g. ["--stat"]
+	pub regex_search: bool,        // `/` queries are regexes unless they start with \V
+	pub max_lines: Option<usize>,  // files longer than this need --force to blame, or 0 for no limit
+	pub wrap_scroll: bool,         // `j` on the last line goes to the first, and `k` on the first to the last
+}
+
+// how far page_down and page_up move: "half" (the default) or "full" a screen, or a number of lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "toml::Value")]
+pub enum PageScroll {
+	#[default]
+	Half,
+	Full,
+	Lines(u16),
+}
+
+impl PageScroll {
+	pub fn lines(self, height: u16) -> i16 {
+		let lines = match self {
+			PageScroll::Half => height / 2,
+			PageScroll::Full => height,
+			PageScroll::Lines(lines) => lines,
+		};
+		i16::try_from(lines.max(1)).unwrap_or(i16::MAX)
+	}
+}
+
+impl FromStr for PageScroll {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<PageScroll, String> {
+		match s {
+			"half" => Ok(PageScroll::Half),
+			"full" => Ok(PageScroll::Full),
+			_ => match s.parse() {
+				Ok(lines) if lines > 0 => Ok(PageScroll::Lines(lines)),
+				_ => Err(format!(
+					"invalid page scroll {} (expected half, full, or a number of lines)",
+					s
+				)),
+			},
+		}
+	}
+}
+
+impl TryFrom<toml::Value> for PageScroll {
+	type Error = String;
+
+	fn try_from(value: toml::Value) -> Result<PageScroll, String> {
+		match value {
+			toml::Value::String(s) => s.parse(),
+			toml::Value::Integer(lines) => lines.to_string().parse(),
+			_ => Err(format!(
+				"invalid page_scroll {} (expected half, full, or a number of lines)",
+				value
+			)),
+		}
+	}
+}
+
+// colors are names, palette indexes, or #rrggbb; unset ones come from the named theme
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+	pub name: Option<String>, // dark (the default) or light
+	pub title: Option<String>,
+	pub selection: Option<String>,
+	pub inactive_selection: Option<String>, // while the right panel or comparison has focus
+	pub gutter: Option<String>,
+	pub search_match: Option<String>,
+	pub age: Option<Vec<String>>,  // heatmap colors from oldest to newest
+	pub diff_file: Option<String>, // the --- and +++ lines of diffs
+	pub diff_hunk: Option<String>, // @@ lines
+	pub diff_added: Option<String>,
+	pub diff_removed: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColumnLayout {
+	pub name: String,
+	pub columns: Vec<Column>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+	LineNumbers,
+	Badges,
+	Sha,
+	Author,
+	Date,
+	Summary,
+}
+
+pub const DEFAULT_LAYOUTS: [(&str, &[Column]); 3] = [
+	("minimal", &[Column::Sha]),
+	(
+		"author and date",
+		&[Column::LineNumbers, Column::Sha, Column::Author, Column::Date],
+	),
+	(
+		"full",
+		&[
+			Column::LineNumbers,
+			Column::Badges,
+			Column::Sha,
+			Column::Author,
+			Column::Date,
+			Column::Summary,
+		],
+	),
+];
+
+pub fn default_layouts() -> Vec<ColumnLayout> {
+	DEFAULT_LAYOUTS
+		.iter()
+		.map(|(name, columns)| ColumnLayout {
+			name: name.to_string(),
+			columns: columns.to_vec(),
+		})
+		.collect()
+}
+
+// which lines `T` dims
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DimConfig {
+	pub days: u64,   // lines from commits older than this are dimmed
+	pub newer: bool, // dim the lines newer than days instead
+}
+
+impl Default for DimConfig {
+	fn default() -> DimConfig {
+		DimConfig { days: 90, newer: false }
+	}
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum KeyNames {
+	One(String),
+	Many(Vec<String>),
+}
+
+impl KeyNames {
+	pub fn names(&self) -> &[String] {
+		match self {
+			KeyNames::One(name) => std::slice::from_ref(name),
+			KeyNames::Many(names) => names,
+		}
+	}
+}
+
+// $XDG_CONFIG_HOME/git-whence/config.toml, defaulting to ~/.config
+pub fn path() -> Option<PathBuf> {
+	let config_home = match env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+	};
+	Some(config_home.join("git-whence").join("config.toml"))
+}
+
+// a missing config file is the same as an empty one
+pub fn load() -> Result<Config, Box<dyn error::Error>> {
+	let Some(path) = path() else {
+		return Ok(Config::default());
+	};
+	match fs::read_to_string(&path) {
+		Ok(contents) => toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e).into()),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+		Err(e) => Err(format!("{}: {}", path.display(), e).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Column, Config, DimConfig, PageScroll};
+
+	#[test]
+	fn parse() {
+		let config: Config = toml::from_str(
+			r#"
+[keys]
+scroll_down = ["j", "ctrl-n"]
+blame_parent = "p"
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.keys["scroll_down"].names(), ["j", "ctrl-n"]);
+		assert_eq!(config.keys["blame_parent"].names(), ["p"]);
+
+		assert!(toml::from_str::<Config>("[kyes]").is_err());
+
+		assert_eq!(
+			toml::from_str::<Config>("page_scroll = \"full\"").unwrap().page_scroll,
+			Some(PageScroll::Full)
+		);
+		assert_eq!(
+			toml::from_str::<Config>("page_scroll = 10").unwrap().page_scroll,
+			Some(PageScroll::Lines(10))
+		);
+		assert!(toml::from_str::<Config>("page_scroll = 0").is_err());
+		assert!(toml::from_str::<Config>("page_scroll = \"most\"").is_err());
+
+		assert_eq!(toml::from_str::<Config>("max_lines = 0").unwrap().max_lines, Some(0));
+		assert_eq!(toml::from_str::<Config>("").unwrap().max_lines, None);
+		assert!(toml::from_str::<Config>("max_lines = -1").is_err());
+
+		let config: Config = toml::from_str("[dim]\nnewer = true").unwrap();
+		assert_eq!(config.dim, DimConfig { days: 90, newer: true });
+
+		let config: Config = toml::from_str(
+			r#"
+[[layouts]]
+name = "who"
+columns = ["author", "line_numbers"]
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.layouts[0].columns, [Column::Author, Column::LineNumbers]);
+		assert!(toml::from_str::<Config>("[[layouts]]\nname = \"x\"\ncolumns = [\"email\"]").is_err());
+	}
+}
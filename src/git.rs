@@ -1,195 +1,1419 @@
-use ansi_to_tui::IntoText;
-use git2::{DiffLineType, Oid, Repository};
+use encoding_rs::WINDOWS_1252;
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
 use std::{
-	error,
-	path::{Path, PathBuf},
-	process, time, vec,
-};
-use tui::{
-	style::{Color, Style},
-	text::{Line, Span, Text},
+	borrow::Cow,
+	collections::HashMap,
+	error, fs,
+	io::{self, BufRead, Read},
+	path::{Component, Path, PathBuf},
+	process,
+	sync::{mpsc, Arc},
+	thread, time, vec,
 };
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::highlight::{CodeSpan, Highlighter};
 
-#[derive(Debug)]
-pub struct BlameHunk<'a> {
-	pub line: Line<'a>,
+#[derive(Clone, Debug)]
+pub struct BlameHunk {
 	pub commit: Oid,
+	pub commit_time: time::SystemTime,
+	pub author: String,
+	pub author_email: String,
+	pub summary: String,
 	pub path: Option<PathBuf>,
+	pub line_num: i32,
+	pub first_in_hunk: bool, // only the first line of each hunk shows the commit
+	pub code: Vec<CodeSpan>,
+	pub encoding: Option<&'static str>, // what the code was decoded from when it isn't UTF-8
+}
+
+impl BlameHunk {
+	// like the `A` filter, "name <email>" containing query with smartcase
+	pub fn matches_author(&self, query: &str) -> bool {
+		!query.is_empty() && smartcase_contains(&format!("{} <{}>", self.author, self.author_email), query)
+	}
+
+	pub fn code_width(&self) -> usize {
+		self.code.iter().map(|span| span.text.width()).sum()
+	}
+
+	// the code without its colors
+	pub fn code_text(&self) -> String {
+		self.code.iter().map(|span| span.text.as_str()).collect()
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BlameOptions {
+	pub ignore_whitespace: bool,
+	pub detect_copies: bool, // from other files in any commit, which is much slower
+	pub ignore_revs: Vec<Oid>,
+	pub range: Option<(usize, usize)>, // 1-based and inclusive, like git blame -L
+	pub tab_width: usize,              // tabs in the code are expanded to the next multiple of this
+}
+
+impl Default for BlameOptions {
+	fn default() -> BlameOptions {
+		BlameOptions {
+			ignore_whitespace: false,
+			detect_copies: false,
+			ignore_revs: vec![],
+			range: None,
+			tab_width: 8,
+		}
+	}
+}
+
+#[derive(Clone, Default)]
+pub struct LogOptions {
+	pub first_parent: bool, // like git log --first-parent, skipping the commits merges brought in
+	pub args: Vec<String>,  // more git log options, after the defaults so they can override them
 }
 
-pub fn blame<'a>(
-	repo: &'a Repository,
+// a zero start_commit blames the working tree, where uncommitted lines also have a zero commit
+pub fn blame(
+	repo: &Repository,
 	rel_path: &Path,
 	start_commit: Oid,
-) -> Result<Vec<BlameHunk<'a>>, Box<dyn error::Error>> {
-	let output = process::Command::new("git")
-		.args([
-			"blame",
-			"--porcelain",
-			rel_path.to_str().unwrap(),
-			&start_commit.to_string(),
-		])
-		.current_dir(repo.path())
-		.output()?;
+	options: &BlameOptions,
+	highlighter: Option<&Highlighter>,
+) -> Result<Vec<BlameHunk>, Box<dyn error::Error>> {
+	let output = blame_command(repo, rel_path, start_commit, options, "--porcelain")?.output()?;
 	if !output.status.success() {
-		return Err(std::str::from_utf8(&output.stderr)?.into());
+		return Err(String::from_utf8_lossy(&output.stderr).into());
 	}
-	let blame_output = std::str::from_utf8(&output.stdout)?;
-	let blame = crate::git_blame_porcelain::parse_blame_porcelain(blame_output)?;
+	// the same heuristic git uses
+	if output.stdout.contains(&0) {
+		return Err(format!("{} is a binary file", rel_path.display()).into());
+	}
+	let (blame_output, encodings) = decode_blame_output(&output.stdout);
+	let blame = crate::git_blame_porcelain::parse_blame_porcelain(&blame_output)?;
 
+	let code_lines: Vec<&str> = blame.iter().flat_map(|b| b.code.iter().copied()).collect();
+	let mut code = format_code(rel_path, &code_lines, options.tab_width, highlighter).into_iter();
+	let mut encodings = encodings.into_iter();
 	let mut out = vec![];
-	let now = time::SystemTime::now();
-	let duration_formatter = timeago::Formatter::new();
 	for b in blame {
-		let commit_time = b.info.commit_time;
-		let time_display = duration_formatter.convert(now.duration_since(commit_time).unwrap_or_default());
-		let mut spans = vec![
-			Span::styled(format!("{:.8}", b.commit), Style::default().fg(Color::Yellow)),
-			Span::raw(format!(" {}", fmt_width(b.info.author, 12))),
-			Span::styled(
-				format!(" {}", fmt_width(&time_display, 13)),
-				Style::default().fg(Color::LightRed),
-			),
-		];
-		spans.append(&mut format_line_num_and_code(b.line_num, b.code[0]));
-		let line_path = b.info.path;
-		out.push(BlameHunk {
-			line: Line::from(spans),
-			commit: Oid::from_str(b.commit)?,
-			path: line_path.map(|p| p.to_owned()),
-		});
-
-		for i in 1..b.code.len() {
-			let mut spans = vec![Span::raw(" ".repeat(35))];
-			let line_num = b.line_num + i32::try_from(i).unwrap();
-			spans.append(&mut format_line_num_and_code(line_num, b.code[i]));
+		let commit = Oid::from_str(b.commit)?;
+		for i in 0..b.code.len() {
 			out.push(BlameHunk {
-				line: Line::from(spans),
-				commit: Oid::from_str(b.commit)?,
-				path: line_path.map(|p| p.to_owned()),
+				commit,
+				commit_time: b.info.commit_time,
+				author: b.info.author.to_owned(),
+				author_email: b.info.author_mail.to_owned(),
+				summary: b.info.summary.to_owned(),
+				path: b.info.path.map(|p| p.to_owned()),
+				line_num: b.line_num + i32::try_from(i).unwrap(),
+				first_in_hunk: i == 0,
+				code: code.next().unwrap(),
+				encoding: encodings.next().flatten(),
 			});
 		}
 	}
 	Ok(out)
 }
 
-fn fmt_width(s: &str, width: usize) -> String {
-	let mut out = String::new();
-	match s.char_indices().nth(width) {
-		None => out.push_str(s),
-		Some((i, _)) => out.push_str(&s[..i]),
-	};
-	if out.len() < width {
-		out.push_str(&" ".repeat(width - out.len()));
+// git blame with the options, in format (--porcelain or --incremental)
+fn blame_command(
+	repo: &Repository,
+	rel_path: &Path,
+	start_commit: Oid,
+	options: &BlameOptions,
+	format: &str,
+) -> Result<process::Command, Box<dyn error::Error>> {
+	let mut cmd = process::Command::new("git");
+	cmd.args(["blame", format]);
+	if options.ignore_whitespace {
+		cmd.arg("-w");
 	}
-	out
+	if options.detect_copies {
+		cmd.args(["-C", "-C", "-C"]);
+	}
+	for rev in &options.ignore_revs {
+		cmd.args(["--ignore-rev", &rev.to_string()]);
+	}
+	if let Some((start, end)) = options.range {
+		cmd.arg(format!("-L{},{}", start, end));
+	}
+	cmd.arg(rel_path.to_str().unwrap());
+	if start_commit.is_zero() {
+		cmd.current_dir(
+			repo.workdir()
+				.ok_or("can't blame the working tree of a bare repository")?,
+		);
+	} else {
+		cmd.arg(start_commit.to_string()).current_dir(repo.path());
+	}
+	Ok(cmd)
 }
 
-fn format_line_num_and_code(line_num: i32, line: &str) -> Vec<Span<'static>> {
-	vec![
-		Span::styled(format!(" {:4} ", line_num), Style::default().fg(Color::DarkGray)),
-		Span::raw(line.replace('\t', "    ")),
-	]
+// the code's spans, with tabs expanded
+fn format_code(
+	rel_path: &Path,
+	code_lines: &[&str],
+	tab_width: usize,
+	highlighter: Option<&Highlighter>,
+) -> Vec<Vec<CodeSpan>> {
+	// highlighting is stateful across lines, so do the whole file at once
+	let mut highlighted = highlighter
+		.and_then(|h| h.highlight(rel_path, code_lines))
+		.map(|lines| lines.into_iter());
+	code_lines
+		.iter()
+		.map(|line| match highlighted.as_mut().and_then(|lines| lines.next()) {
+			Some(spans) => expand_tabs(spans, tab_width),
+			None => expand_tabs(vec![CodeSpan::plain(*line)], tab_width),
+		})
+		.collect()
 }
 
-pub fn show(repo: &Repository, commit_id: Oid) -> Text<'static> {
-	let commit = match repo.find_commit(commit_id) {
-		Ok(commit) => commit,
-		Err(e) => return Text::raw(e.to_string()),
-	};
-	let diff = match diff_for_commit(repo, &commit) {
-		Ok(diff) => diff,
-		Err(e) => return Text::raw(e.to_string()),
-	};
-	let author = commit.author();
-	let commit_time = commit.time();
-	let time = chrono::DateTime::from_timestamp(commit_time.seconds(), 0).unwrap();
-	let mut lines = vec![
-		Line::from(Span::styled(
-			commit.id().to_string(),
-			Style::default().fg(Color::Yellow),
-		)),
-		Line::from(format!(
-			"author: {} <{}>",
-			author.name().unwrap_or_default(),
-			author.email().unwrap_or_default()
-		)),
-		Line::from(format!("date: {}", time.with_timezone(&chrono::Local))),
-		Line::default(),
-		Line::from(commit.summary().unwrap_or_default().to_owned()),
-		Line::default(),
-	];
-	if let Some(body) = commit.body() {
-		push_lines(&mut lines, body, Color::Reset);
-		lines.push(Line::default());
-	}
-	let diff_cb = |_: git2::DiffDelta, _: Option<git2::DiffHunk>, diff_line: git2::DiffLine| -> bool {
-		let content = std::str::from_utf8(diff_line.content()).expect("couldn't decode diff line");
-		let sigil = match diff_line.origin_value() {
-			DiffLineType::Addition => "+",
-			DiffLineType::Deletion => "-",
-			DiffLineType::Context => " ",
-			_ => "",
-		};
-		let line = format!("{}{}", sigil, content.replace('\t', "    ").trim_end_matches('\n'));
-		let color = match diff_line.origin_value() {
-			DiffLineType::FileHeader => Color::Cyan,
-			DiffLineType::HunkHeader => Color::Blue,
-			DiffLineType::Addition | DiffLineType::AddEOFNL => Color::Green,
-			DiffLineType::Deletion | DiffLineType::DeleteEOFNL => Color::Red,
-			_ => Color::Reset,
+// git blame --incremental running in the background, which reports each hunk as soon as git finds it instead of all
+// of them at the end. dropping it kills git
+pub struct BlameStream {
+	child: process::Child,
+	output: mpsc::Receiver<BlameChunk>,
+}
+
+enum BlameChunk {
+	Lines(Vec<BlameHunk>), // all of the code, before any of it is blamed
+	Hunk(Vec<BlameHunk>),
+	Joined(Vec<i32>), // lines that continue the hunk above them, as git blame --porcelain would show them
+	Done(Vec<u8>),    // stdout hit EOF; this is stderr
+	Failed(String),
+}
+
+// where an --incremental hunk starts in the file and in its commit, for joining it to the hunk above
+struct HunkOrigin {
+	line_num: i32,
+	orig_line_num: i32,
+	len: i32,
+	commit: Oid,
+	path: Option<PathBuf>,
+}
+
+impl BlameStream {
+	// like blame, but what git has found so far can be taken with poll while it runs
+	pub fn spawn(
+		repo: &Repository,
+		rel_path: &Path,
+		start_commit: Oid,
+		options: &BlameOptions,
+		highlighter: Option<Arc<Highlighter>>,
+	) -> Result<BlameStream, Box<dyn error::Error>> {
+		let content = match start_commit.is_zero() {
+			true => {
+				let workdir = repo
+					.workdir()
+					.ok_or("can't blame the working tree of a bare repository")?;
+				fs::read(workdir.join(rel_path))?
+			}
+			false => {
+				let entry = repo.find_commit(start_commit)?.tree()?.get_path(rel_path)?;
+				repo.find_blob(entry.id())?.content().to_owned()
+			}
 		};
-		push_lines(&mut lines, &line, color);
-		true
+		// the same heuristic git uses
+		if content.contains(&0) {
+			return Err(format!("{} is a binary file", rel_path.display()).into());
+		}
+		let mut child = blame_command(repo, rel_path, start_commit, options, "--incremental")?
+			.stdin(process::Stdio::null())
+			.stdout(process::Stdio::piped())
+			.stderr(process::Stdio::piped())
+			.spawn()?;
+		let (stdout, mut stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+		let (sender, output) = mpsc::channel();
+		let (rel_path, options) = (rel_path.to_owned(), options.clone());
+		thread::spawn(move || {
+			// stderr is read all at once so git can't block on it while stdout is read
+			let stderr_thread = thread::spawn(move || {
+				let mut buf = vec![];
+				_ = stderr.read_to_end(&mut buf);
+				buf
+			});
+			let lines = unblamed_lines(&content, &rel_path, &options, highlighter.as_deref());
+			if sender.send(BlameChunk::Lines(lines.clone())).is_err() {
+				return; // dropped
+			}
+			let mut commits = HashMap::new();
+			let mut origins = vec![];
+			let mut stdout = io::BufReader::new(stdout);
+			let mut entry = vec![];
+			loop {
+				let start = entry.len();
+				match stdout.read_until(b'\n', &mut entry) {
+					Ok(0) => break,
+					Ok(_) => {}
+					Err(e) => {
+						_ = sender.send(BlameChunk::Failed(e.to_string()));
+						return;
+					}
+				}
+				// every entry ends with its filename
+				if !entry[start..].starts_with(b"filename ") {
+					continue;
+				}
+				let hunk = match incremental_hunk(&String::from_utf8_lossy(&entry), &lines, &mut commits) {
+					Ok(hunk) => hunk,
+					Err(e) => {
+						_ = sender.send(BlameChunk::Failed(e.to_string()));
+						return;
+					}
+				};
+				entry.clear();
+				origins.push(hunk.0);
+				if sender.send(BlameChunk::Hunk(hunk.1)).is_err() {
+					return;
+				}
+			}
+			// git joins hunks that continue each other before showing them, except with --incremental
+			origins.sort_by_key(|origin| origin.line_num);
+			let joined = origins
+				.windows(2)
+				.filter(|pair| {
+					let (hunk, next) = (&pair[0], &pair[1]);
+					hunk.line_num + hunk.len == next.line_num
+						&& hunk.orig_line_num + hunk.len == next.orig_line_num
+						&& (hunk.commit, &hunk.path) == (next.commit, &next.path)
+				})
+				.map(|pair| pair[1].line_num)
+				.collect();
+			_ = sender.send(BlameChunk::Joined(joined));
+			_ = sender.send(BlameChunk::Done(stderr_thread.join().unwrap_or_default()));
+		});
+		Ok(BlameStream { child, output })
+	}
+
+	// puts what git has found so far into blame, without blocking. None while git is still running, then the error
+	// if it failed
+	pub fn poll(&mut self, blame: &mut Vec<BlameHunk>) -> Option<Result<(), String>> {
+		loop {
+			let chunk = match self.output.try_recv() {
+				Ok(chunk) => chunk,
+				Err(mpsc::TryRecvError::Empty) => return None,
+				Err(mpsc::TryRecvError::Disconnected) => return Some(Err("git blame output thread exited".to_owned())),
+			};
+			match chunk {
+				BlameChunk::Lines(lines) => *blame = lines,
+				BlameChunk::Hunk(hunk) => {
+					let start = (hunk[0].line_num - blame[0].line_num) as usize;
+					for (line, blamed) in blame[start..].iter_mut().zip(hunk) {
+						*line = blamed;
+					}
+				}
+				BlameChunk::Joined(line_nums) => {
+					for line in blame.iter_mut().filter(|line| line_nums.contains(&line.line_num)) {
+						line.first_in_hunk = false;
+					}
+				}
+				BlameChunk::Done(stderr) => {
+					return Some(match self.child.wait() {
+						Ok(status) if status.success() => Ok(()),
+						Ok(_) => Err(String::from_utf8_lossy(&stderr).into_owned()),
+						Err(e) => Err(e.to_string()),
+					})
+				}
+				BlameChunk::Failed(error) => return Some(Err(error)),
+			}
+		}
+	}
+}
+
+impl Drop for BlameStream {
+	fn drop(&mut self) {
+		if let Ok(None) = self.child.try_wait() {
+			_ = self.child.kill();
+		}
+		_ = self.child.wait();
+	}
+}
+
+// the lines of content that git blame will report on, with a zero commit and no author until it does
+fn unblamed_lines(
+	content: &[u8],
+	rel_path: &Path,
+	options: &BlameOptions,
+	highlighter: Option<&Highlighter>,
+) -> Vec<BlameHunk> {
+	let mut lines: Vec<&[u8]> = content.split_inclusive(|&b| b == b'\n').collect();
+	let first = match options.range {
+		Some((start, end)) => {
+			lines.truncate(end);
+			lines.drain(..(start - 1).min(lines.len()));
+			start
+		}
+		None => 1,
 	};
-	if let Err(e) = diff.print(git2::DiffFormat::Patch, diff_cb) {
-		return Text::raw(e.to_string());
+	let decoded: Vec<_> = lines
+		.iter()
+		.map(|line| {
+			let line = line.strip_suffix(b"\n").unwrap_or(line);
+			decode_line(line.strip_suffix(b"\r").unwrap_or(line))
+		})
+		.collect();
+	let code_lines: Vec<&str> = decoded.iter().map(|(line, _)| line.as_ref()).collect();
+	let code = format_code(rel_path, &code_lines, options.tab_width, highlighter);
+	code.into_iter()
+		.zip(&decoded)
+		.enumerate()
+		.map(|(i, (code, (_, encoding)))| BlameHunk {
+			commit: Oid::zero(),
+			commit_time: time::UNIX_EPOCH,
+			author: String::new(),
+			author_email: String::new(),
+			summary: String::new(),
+			path: None,
+			line_num: i32::try_from(first + i).unwrap(),
+			first_in_hunk: false,
+			code,
+			encoding: *encoding,
+		})
+		.collect()
+}
+
+// the lines of an --incremental entry, with their code from lines. commits has the info of the commits seen so far,
+// since git only sends it the first time
+fn incremental_hunk(
+	entry: &str,
+	lines: &[BlameHunk],
+	commits: &mut HashMap<Oid, (time::SystemTime, String, String, String)>,
+) -> Result<(HunkOrigin, Vec<BlameHunk>), Box<dyn error::Error>> {
+	let entry = crate::git_blame_porcelain::parse_incremental_entry(entry)?;
+	let commit = Oid::from_str(entry.commit)?;
+	if let Some(info) = entry.info {
+		let (author, email, summary) = (info.author, info.author_mail, info.summary);
+		commits.insert(
+			commit,
+			(
+				info.commit_time,
+				author.to_owned(),
+				email.to_owned(),
+				summary.to_owned(),
+			),
+		);
 	}
-	Text::from(lines)
+	let (commit_time, author, author_email, summary) = commits.get(&commit).ok_or("expected commit info")?;
+	let first = lines.first().map_or(1, |line| line.line_num);
+	let start = usize::try_from(entry.line_num - first)?;
+	let code = lines
+		.get(start..start + usize::try_from(entry.num_lines)?)
+		.ok_or("blamed past the end of the file")?;
+	let path = Some(entry.path.to_owned());
+	let hunk = code
+		.iter()
+		.enumerate()
+		.map(|(i, line)| BlameHunk {
+			commit,
+			commit_time: *commit_time,
+			author: author.clone(),
+			author_email: author_email.clone(),
+			summary: summary.clone(),
+			path: path.clone(),
+			line_num: line.line_num,
+			first_in_hunk: i == 0,
+			code: line.code.clone(),
+			encoding: line.encoding,
+		})
+		.collect();
+	let origin = HunkOrigin {
+		line_num: entry.line_num,
+		orig_line_num: entry.orig_line_num,
+		len: entry.num_lines,
+		commit,
+		path,
+	};
+	Ok((origin, hunk))
 }
 
-fn diff_for_commit<'a>(repo: &'a Repository, commit: &git2::Commit<'a>) -> Result<git2::Diff<'a>, git2::Error> {
-	let parent = commit.parent(0)?;
-	return repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&commit.tree()?), None);
+// git blame passes the file's bytes through, so decode each line on its own, falling back to windows-1252
+// (a superset of latin-1) for the ones that aren't UTF-8. also returns the fallback used for each code line
+fn decode_blame_output(output: &[u8]) -> (String, Vec<Option<&'static str>>) {
+	let mut decoded = String::with_capacity(output.len());
+	let mut encodings = vec![];
+	for line in output.split_inclusive(|&b| b == b'\n') {
+		let (text, encoding) = decode_line(line);
+		decoded.push_str(&text);
+		if line.starts_with(b"\t") {
+			encodings.push(encoding);
+		}
+	}
+	(decoded, encodings)
 }
 
-fn push_lines(lines: &mut Vec<Line>, s: &str, color: Color) {
-	for line in s.split('\n') {
-		lines.push(Line::from(Span::styled(line.to_owned(), Style::default().fg(color))));
+// a line of the file as UTF-8 if it is, or else windows-1252, which it's then returned with
+fn decode_line(line: &[u8]) -> (Cow<'_, str>, Option<&'static str>) {
+	match std::str::from_utf8(line) {
+		Ok(line) => (Cow::Borrowed(line), None),
+		Err(_) => (
+			WINDOWS_1252.decode_without_bom_handling(line).0,
+			Some(WINDOWS_1252.name()),
+		),
 	}
 }
 
-pub fn log_follow(repo: &Repository, rel_path: &Path, line_num: usize, start_commit: Oid) -> Text<'static> {
-	let repo_path = repo.workdir().unwrap();
-	let output = process::Command::new("git")
-		.args([
+// parses a .git-blame-ignore-revs file, returning the OIDs and a warning for each invalid line
+pub fn parse_ignore_revs(contents: &str) -> (Vec<Oid>, Vec<String>) {
+	let mut revs = vec![];
+	let mut warnings = vec![];
+	for (i, line) in contents.lines().enumerate() {
+		let line = line.split('#').next().unwrap().trim();
+		if line.is_empty() {
+			continue;
+		}
+		match Oid::from_str(line) {
+			Ok(oid) if line.len() == 40 => revs.push(oid),
+			_ => warnings.push(format!("line {}: invalid object name {}", i + 1, line)),
+		}
+	}
+	(revs, warnings)
+}
+
+// s cut or padded to width columns, counting wide characters as two
+pub fn fmt_width(s: &str, width: usize) -> String {
+	let mut out = String::new();
+	let mut used = 0;
+	for c in s.chars() {
+		let c_width = char_width(c);
+		if used + c_width > width {
+			break;
+		}
+		out.push(c);
+		used += c_width;
+	}
+	out.push_str(&" ".repeat(width - used));
+	out
+}
+
+// like the terminal: wide characters like CJK take two columns, and combining marks and control characters none
+pub fn char_width(c: char) -> usize {
+	c.width().unwrap_or(0)
+}
+
+// replaces each tab with spaces up to the next tab stop, counting columns across spans
+fn expand_tabs(spans: Vec<CodeSpan>, tab_width: usize) -> Vec<CodeSpan> {
+	let tab_width = tab_width.max(1);
+	let mut column = 0;
+	spans
+		.into_iter()
+		.map(|mut span| {
+			if !span.text.contains('\t') {
+				column += span.text.width();
+				return span;
+			}
+			let mut expanded = String::with_capacity(span.text.len());
+			for c in span.text.chars() {
+				if c == '\t' {
+					let spaces = tab_width - column % tab_width;
+					expanded.extend(std::iter::repeat_n(' ', spaces));
+					column += spaces;
+				} else {
+					expanded.push(c);
+					column += char_width(c);
+				}
+			}
+			span.text = expanded;
+			span
+		})
+		.collect()
+}
+
+// a query with no uppercase letters matches case-insensitively
+pub fn smartcase_contains(text: &str, query: &str) -> bool {
+	if query.chars().any(char::is_uppercase) {
+		text.contains(query)
+	} else {
+		text.to_lowercase().contains(query)
+	}
+}
+
+// like "3 months ago"
+pub fn fmt_age(time: time::SystemTime) -> String {
+	timeago::Formatter::new().convert(time::SystemTime::now().duration_since(time).unwrap_or_default())
+}
+
+// the commit's author date, relative or in ISO 8601
+pub fn commit_date(repo: &Repository, commit_id: Oid, absolute: bool) -> Option<String> {
+	let when = repo.find_commit(commit_id).ok()?.author().when();
+	if absolute {
+		let time = chrono::DateTime::from_timestamp(when.seconds(), 0)?;
+		Some(time.with_timezone(&chrono::Local).to_rfc3339())
+	} else {
+		Some(fmt_age(git_time(when)))
+	}
+}
+
+pub const DEFAULT_ABBREV: usize = 7;
+
+// core.abbrev, which may also be auto or no
+pub fn configured_abbrev(repo: &Repository) -> usize {
+	let Ok(abbrev) = repo.config().and_then(|config| config.get_string("core.abbrev")) else {
+		return DEFAULT_ABBREV;
+	};
+	match abbrev.as_str() {
+		"no" | "false" => 40,
+		_ => abbrev.parse().map_or(DEFAULT_ABBREV, |n: usize| n.clamp(4, 40)),
+	}
+}
+
+// at least min hex digits, and more if any of the commits needs them to be unambiguous, like git blame
+pub fn unambiguous_abbrev(repo: &Repository, commits: impl IntoIterator<Item = Oid>, min: usize) -> usize {
+	let mut abbrev = min;
+	for commit in commits.into_iter().filter(|commit| !commit.is_zero()) {
+		let hex = commit.to_string();
+		while abbrev < hex.len() {
+			match repo.find_object_by_prefix(&hex[..abbrev], None) {
+				Err(e) if e.code() == git2::ErrorCode::Ambiguous => abbrev += 1,
+				_ => break,
+			}
+		}
+	}
+	abbrev
+}
+
+pub fn git_time(time: git2::Time) -> time::SystemTime {
+	time::UNIX_EPOCH + time::Duration::from_secs(time.seconds().max(0) as u64)
+}
+
+// the history of lines start through end (0-based, inclusive), as git log colors it, or git's error
+pub fn log_follow(
+	repo: &Repository,
+	rel_path: &Path,
+	start: usize,
+	end: usize,
+	start_commit: Oid,
+	options: &LogOptions,
+) -> Result<Vec<u8>, String> {
+	LogFollow::spawn(repo, rel_path, start, end, start_commit, options)
+		.map_err(|e| e.to_string())?
+		.wait()
+}
+
+// git log -L running in the background, which can take minutes on a long history. dropping it kills git
+pub struct LogFollow {
+	child: process::Child,
+	output: mpsc::Receiver<FollowChunk>,
+}
+
+enum FollowChunk {
+	Lines(Vec<u8>),
+	Done(Vec<u8>), // stdout hit EOF; this is stderr
+}
+
+// what LogFollow::poll got from git
+pub enum FollowOutput {
+	Pending,
+	More(Vec<u8>),        // lines of git log's output, with its ANSI colors
+	Done(Option<String>), // the error when git failed, to show instead of what came before
+}
+
+const FOLLOW_CHUNK_LINES: usize = 200;
+// chunks read ahead of what's been taken; after that git blocks on the pipe until more is wanted
+const FOLLOW_CHUNKS_AHEAD: usize = 4;
+
+impl LogFollow {
+	pub fn spawn(
+		repo: &Repository,
+		rel_path: &Path,
+		start: usize,
+		end: usize,
+		start_commit: Oid,
+		options: &LogOptions,
+	) -> io::Result<LogFollow> {
+		let repo_path = repo.workdir().unwrap_or(repo.path());
+		// git log only has committed history, so the working tree follows from HEAD
+		let rev = match start_commit.is_zero() {
+			true => "HEAD".to_owned(),
+			false => start_commit.to_string(),
+		};
+		let mut command = process::Command::new("git");
+		command.args([
 			"log",
 			"--color=always",
 			"--abbrev-commit",
 			"--decorate",
 			"--date=local",
 			"--find-copies",
-			"-L",
-			&format!("{},{}:{}", line_num + 1, line_num + 1, rel_path.display()),
-			&start_commit.to_string(),
-		])
-		.current_dir(repo_path)
-		.output();
+		]);
+		if options.first_parent {
+			command.arg("--first-parent");
+		}
+		let mut child = command
+			.args(&options.args)
+			.args(["-L", &format!("{},{}:{}", start + 1, end + 1, rel_path.display()), &rev])
+			.current_dir(repo_path)
+			.stdin(process::Stdio::null())
+			.stdout(process::Stdio::piped())
+			.stderr(process::Stdio::piped())
+			.spawn()?;
+		let (stdout, mut stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+		let (sender, output) = mpsc::sync_channel(FOLLOW_CHUNKS_AHEAD);
+		thread::spawn(move || {
+			// stderr is read all at once so git can't block on it while stdout waits for the UI
+			let stderr_thread = thread::spawn(move || {
+				let mut buf = vec![];
+				_ = stderr.read_to_end(&mut buf);
+				buf
+			});
+			let mut stdout = io::BufReader::new(stdout);
+			loop {
+				let mut chunk = vec![];
+				for _ in 0..FOLLOW_CHUNK_LINES {
+					match stdout.read_until(b'\n', &mut chunk) {
+						Ok(0) | Err(_) => break,
+						Ok(_) => {}
+					}
+				}
+				if chunk.is_empty() {
+					break;
+				}
+				if sender.send(FollowChunk::Lines(chunk)).is_err() {
+					return; // dropped
+				}
+			}
+			_ = sender.send(FollowChunk::Done(stderr_thread.join().unwrap_or_default()));
+		});
+		Ok(LogFollow { child, output })
+	}
+
+	// the next chunk of history, without blocking
+	pub fn poll(&mut self) -> FollowOutput {
+		match self.output.try_recv() {
+			Ok(chunk) => self.output_for(chunk),
+			Err(mpsc::TryRecvError::Empty) => FollowOutput::Pending,
+			Err(mpsc::TryRecvError::Disconnected) => {
+				FollowOutput::Done(Some("git log output thread exited".to_owned()))
+			}
+		}
+	}
+
+	// all of the history at once
+	pub fn wait(mut self) -> Result<Vec<u8>, String> {
+		let mut history = vec![];
+		loop {
+			let output = match self.output.recv() {
+				Ok(chunk) => self.output_for(chunk),
+				Err(_) => FollowOutput::Done(Some("git log output thread exited".to_owned())),
+			};
+			match output {
+				FollowOutput::Pending => {}
+				FollowOutput::More(more) => history.extend(more),
+				FollowOutput::Done(Some(error)) => return Err(error),
+				FollowOutput::Done(None) => return Ok(history),
+			}
+		}
+	}
+
+	fn output_for(&mut self, chunk: FollowChunk) -> FollowOutput {
+		match chunk {
+			FollowChunk::Lines(lines) => FollowOutput::More(lines),
+			FollowChunk::Done(stderr) => match self.child.wait() {
+				Ok(status) if status.success() => FollowOutput::Done(None),
+				Ok(_) => FollowOutput::Done(Some(String::from_utf8_lossy(&stderr).into_owned())),
+				Err(e) => FollowOutput::Done(Some(e.to_string())),
+			},
+		}
+	}
+}
+
+impl Drop for LogFollow {
+	fn drop(&mut self) {
+		if let Ok(None) = self.child.try_wait() {
+			_ = self.child.kill();
+		}
+		_ = self.child.wait();
+	}
+}
+
+// a commit from file_log
+pub struct FileCommit {
+	pub commit: Oid,
+	pub path: PathBuf, // what the file was called in the commit, which changes across renames
+	pub author: String,
+	pub time: time::SystemTime,
+	pub summary: String,
+}
+
+// the commits in start_commit's history that changed rel_path, newest first and following renames like git log --follow
+pub fn file_log(
+	repo: &Repository,
+	rel_path: &Path,
+	start_commit: Oid,
+	first_parent: bool,
+) -> Result<Vec<FileCommit>, Box<dyn error::Error>> {
+	Ok(FileLog::spawn(repo, rel_path, start_commit, first_parent)?.wait()?)
+}
+
+// git log --follow running in the background, which takes a while on a long history. dropping it kills git
+pub struct FileLog {
+	child: process::Child,
+	rel_path: PathBuf,
+	output: mpsc::Receiver<(Vec<u8>, Vec<u8>)>, // stdout and stderr, once git closes them
+}
+
+impl FileLog {
+	pub fn spawn(repo: &Repository, rel_path: &Path, start_commit: Oid, first_parent: bool) -> io::Result<FileLog> {
+		let rev = match start_commit.is_zero() {
+			true => "HEAD".to_owned(),
+			false => start_commit.to_string(),
+		};
+		let mut command = process::Command::new("git");
+		command.args([
+			"-c",
+			"core.quotePath=false",
+			"log",
+			"--follow",
+			"--name-only",
+			"--format=%x1e%H%x00%aN%x00%at%x00%s",
+		]);
+		if first_parent {
+			command.arg("--first-parent");
+		}
+		let mut child = command
+			.args([&rev, "--"])
+			.arg(rel_path)
+			.current_dir(repo.workdir().unwrap_or(repo.path()))
+			.stdin(process::Stdio::null())
+			.stdout(process::Stdio::piped())
+			.stderr(process::Stdio::piped())
+			.spawn()?;
+		let (mut stdout, mut stderr) = (child.stdout.take().unwrap(), child.stderr.take().unwrap());
+		let (sender, output) = mpsc::channel();
+		thread::spawn(move || {
+			// both are read at once so git can't block on either
+			let stderr_thread = thread::spawn(move || {
+				let mut buf = vec![];
+				_ = stderr.read_to_end(&mut buf);
+				buf
+			});
+			let mut buf = vec![];
+			_ = stdout.read_to_end(&mut buf);
+			_ = sender.send((buf, stderr_thread.join().unwrap_or_default()));
+		});
+		Ok(FileLog {
+			child,
+			rel_path: rel_path.to_owned(),
+			output,
+		})
+	}
+
+	// the commits once git has finished, without blocking
+	pub fn poll(&mut self) -> Option<Result<Vec<FileCommit>, String>> {
+		match self.output.try_recv() {
+			Ok(output) => Some(self.commits(output)),
+			Err(mpsc::TryRecvError::Empty) => None,
+			Err(mpsc::TryRecvError::Disconnected) => Some(Err("git log output thread exited".to_owned())),
+		}
+	}
+
+	pub fn wait(mut self) -> Result<Vec<FileCommit>, String> {
+		match self.output.recv() {
+			Ok(output) => self.commits(output),
+			Err(_) => Err("git log output thread exited".to_owned()),
+		}
+	}
+
+	fn commits(&mut self, (stdout, stderr): (Vec<u8>, Vec<u8>)) -> Result<Vec<FileCommit>, String> {
+		match self.child.wait() {
+			Ok(status) if status.success() => {
+				parse_file_log(&String::from_utf8_lossy(&stdout), &self.rel_path).map_err(|e| e.to_string())
+			}
+			Ok(_) => Err(String::from_utf8_lossy(&stderr).into_owned()),
+			Err(e) => Err(e.to_string()),
+		}
+	}
+}
+
+impl Drop for FileLog {
+	fn drop(&mut self) {
+		if let Ok(None) = self.child.try_wait() {
+			_ = self.child.kill();
+		}
+		_ = self.child.wait();
+	}
+}
+
+fn parse_file_log(output: &str, rel_path: &Path) -> Result<Vec<FileCommit>, Box<dyn error::Error>> {
+	let mut commits = vec![];
+	let mut path = rel_path.to_owned();
+	for record in output.split('\x1e').filter(|record| !record.is_empty()) {
+		let mut lines = record.lines();
+		let mut fields = lines.next().unwrap_or_default().splitn(4, '\0');
+		let mut field = || fields.next().ok_or("unexpected git log output");
+		let commit = Oid::from_str(field()?)?;
+		let author = field()?.to_owned();
+		let time = time::UNIX_EPOCH + time::Duration::from_secs(field()?.parse()?);
+		let summary = field()?.to_owned();
+		// merges don't list the file, so they keep the name from the newer commit
+		if let Some(name) = lines.rfind(|line| !line.is_empty()) {
+			path = PathBuf::from(name);
+		}
+		commits.push(FileCommit {
+			commit,
+			path: path.clone(),
+			author,
+			time,
+			summary,
+		});
+	}
+	Ok(commits)
+}
 
-	let buf = match output {
-		Ok(o) => {
-			if o.status.success() {
-				o.stdout
-			} else {
-				o.stderr
+// lines in path at commit, or in the working tree for a zero commit, like wc -l but counting a last line without a
+// newline too
+pub fn line_count(repo: &Repository, commit: Oid, path: &Path) -> Result<usize, Box<dyn error::Error>> {
+	let count = |content: &[u8]| {
+		let newlines = content.iter().filter(|&&b| b == b'\n').count();
+		newlines + usize::from(!content.is_empty() && !content.ends_with(b"\n"))
+	};
+	if commit.is_zero() {
+		let workdir = repo.workdir().ok_or("a bare repository has no working tree")?;
+		return Ok(count(&fs::read(workdir.join(path))?));
+	}
+	let entry = repo.find_commit(commit)?.tree()?.get_path(path)?;
+	Ok(count(repo.find_blob(entry.id())?.content()))
+}
+
+// where line (1-based) of path at commit is in the working tree's copy of worktree_path, and whether it's unchanged
+// there. a changed line maps to where its replacement starts, and a deleted one to the line after it
+pub fn worktree_line(
+	repo: &Repository,
+	commit: Oid,
+	path: &Path,
+	worktree_path: &Path,
+	line: usize,
+) -> Result<(usize, bool), Box<dyn error::Error>> {
+	let workdir = repo.workdir().ok_or("a bare repository has no working tree")?;
+	let new = fs::read(workdir.join(worktree_path))?;
+	if commit.is_zero() {
+		return Ok((line, true));
+	}
+	let entry = repo.find_commit(commit)?.tree()?.get_path(path)?;
+	Ok(map_line(repo.find_blob(entry.id())?.content(), &new, line)?)
+}
+
+fn map_line(old: &[u8], new: &[u8], line: usize) -> Result<(usize, bool), git2::Error> {
+	let mut options = git2::DiffOptions::new();
+	options.context_lines(0);
+	let patch = git2::Patch::from_buffers(old, None, new, None, Some(&mut options))?;
+	let mut offset = 0;
+	for i in 0..patch.num_hunks() {
+		let (hunk, _) = patch.hunk(i)?;
+		let (old_start, old_lines) = (hunk.old_start() as usize, hunk.old_lines() as usize);
+		let new_lines = hunk.new_lines() as isize;
+		if old_lines == 0 {
+			// an insertion after old_start
+			if line <= old_start {
+				break;
 			}
+		} else if line < old_start {
+			break;
+		} else if line < old_start + old_lines {
+			// a deletion's new_start is the line before it
+			let new_start = hunk.new_start() as usize + usize::from(new_lines == 0);
+			return Ok((new_start, false));
+		}
+		offset += new_lines - old_lines as isize;
+	}
+	Ok((line.saturating_add_signed(offset), true))
+}
+
+pub fn file_exists(repo: &Repository, commit: Oid, path: &Path) -> Result<bool, git2::Error> {
+	match repo.find_commit(commit)?.tree()?.get_path(path) {
+		Ok(_) => Ok(true),
+		Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(false),
+		Err(e) => Err(e),
+	}
+}
+
+// the newest commit in the history of commit that has path, for blaming a file that's since been deleted. only the
+// first LAST_COMMIT_MAX_WALK commits are looked at, since a path that never existed would walk the whole history
+pub fn last_commit_with(repo: &Repository, commit: Oid, path: &Path) -> Result<Option<Oid>, git2::Error> {
+	let mut walk = repo.revwalk()?;
+	walk.set_sorting(git2::Sort::TIME)?;
+	walk.push(commit)?;
+	for id in walk.take(LAST_COMMIT_MAX_WALK) {
+		let id = id?;
+		if repo.find_commit(id)?.tree()?.get_path(path).is_ok() {
+			return Ok(Some(id));
 		}
-		Err(e) => {
-			return Text::raw(e.to_string());
+	}
+	Ok(None)
+}
+
+pub const LAST_COMMIT_MAX_WALK: usize = 10000;
+
+// the newest commit in commit's first-parent history committed before cutoff (unix seconds), like the branch was then
+pub fn commit_before(repo: &Repository, commit: Oid, cutoff: i64) -> Result<Option<Oid>, git2::Error> {
+	let mut walk = repo.revwalk()?;
+	walk.simplify_first_parent()?;
+	walk.push(commit)?;
+	for id in walk {
+		let id = id?;
+		if repo.find_commit(id)?.time().seconds() < cutoff {
+			return Ok(Some(id));
 		}
+	}
+	Ok(None)
+}
+
+// the file git should blame for path at commit: symlinks are followed to their targets in the repo, and paths that
+// aren't files, like submodules and directories, are errors. missing paths are left to the blame to report
+pub fn resolve_path(repo: &Repository, commit: Oid, path: &Path) -> Result<PathBuf, String> {
+	// the working tree's files are close enough to HEAD's
+	let commit = match commit.is_zero() {
+		true => match repo.head().and_then(|head| head.peel_to_commit()) {
+			Ok(head) => head,
+			Err(_) => return Ok(path.to_owned()), // nothing committed yet
+		},
+		false => repo.find_commit(commit).map_err(|e| e.message().to_owned())?,
 	};
-	match buf.into_text() {
-		Ok(t) => t,
-		Err(e) => Text::raw(format!("ansi_to_tui:\n{}", e)),
+	let tree = commit.tree().map_err(|e| e.message().to_owned())?;
+	let mut path = path.to_owned();
+	for _ in 0..MAX_SYMLINKS {
+		let entry = match tree.get_path(&path) {
+			Ok(entry) => entry,
+			Err(_) => return Ok(path),
+		};
+		match entry.filemode() {
+			0o120000 => {
+				let blob = entry.to_object(repo).and_then(|object| object.peel_to_blob());
+				let target = String::from_utf8_lossy(blob.map_err(|e| e.message().to_owned())?.content()).into_owned();
+				let parent = path.parent().unwrap_or(Path::new(""));
+				path = normalize(&parent.join(&target))
+					.ok_or_else(|| format!("{} is a symlink to {} outside the repository", path.display(), target))?;
+			}
+			0o160000 => {
+				return Err(format!(
+					"{} is a submodule; blame files inside it from its directory",
+					path.display()
+				))
+			}
+			0o040000 => return Err(format!("{} is a directory", path.display())),
+			_ => return Ok(path),
+		}
+	}
+	Err(format!("{} has too many levels of symlinks", path.display()))
+}
+
+const MAX_SYMLINKS: usize = 8;
+
+// resolves . and .. without touching the disk, or None if the path leaves the repository
+pub fn normalize(path: &Path) -> Option<PathBuf> {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::Normal(name) => normalized.push(name),
+			Component::CurDir => {}
+			Component::ParentDir if normalized.pop() => {}
+			_ => return None, // absolute, or .. past the root
+		}
+	}
+	Some(normalized)
+}
+
+// every file in the commit's tree, in tree order
+pub fn tree_files(repo: &Repository, commit: Oid) -> Result<Vec<String>, Box<dyn error::Error>> {
+	// the working tree's files are close enough to HEAD's
+	let tree = match commit.is_zero() {
+		true => repo.head()?.peel_to_tree()?,
+		false => repo.find_commit(commit)?.tree()?,
+	};
+	let mut files = vec![];
+	tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+		if entry.kind() == Some(ObjectType::Blob) {
+			if let Some(name) = entry.name() {
+				files.push(format!("{}{}", dir, name));
+			}
+		}
+		TreeWalkResult::Ok
+	})?;
+	Ok(files)
+}
+
+pub fn commit_web_url(repo: &Repository, commit: Oid) -> Result<String, Box<dyn error::Error>> {
+	let base = origin_web_url(repo)?;
+	Ok(format!("{}{}/commit/{}", base, gitlab_separator(&base), commit))
+}
+
+// a link to line_num (1-based) of path as of commit
+pub fn blob_web_url(
+	repo: &Repository,
+	commit: Oid,
+	path: &Path,
+	line_num: i32,
+) -> Result<String, Box<dyn error::Error>> {
+	let base = origin_web_url(repo)?;
+	let path = path.to_str().ok_or("path is not valid UTF-8")?;
+	Ok(format!(
+		"{}{}/blob/{}/{}#L{}",
+		base,
+		gitlab_separator(&base),
+		commit,
+		escape_url_path(path),
+		line_num
+	))
+}
+
+fn origin_web_url(repo: &Repository) -> Result<String, Box<dyn error::Error>> {
+	let remote = repo.find_remote("origin")?;
+	let remote_url = remote.url().ok_or("origin remote URL is not valid UTF-8")?;
+	Ok(web_base_url(remote_url).ok_or_else(|| format!("couldn't parse remote URL {}", remote_url))?)
+}
+
+// gitlab puts its pages under /-/ so they can't collide with subgroup names
+fn gitlab_separator(base: &str) -> &'static str {
+	if base.starts_with("https://gitlab.") {
+		"/-"
+	} else {
+		""
+	}
+}
+
+// only the characters that would otherwise end the path or break the link
+fn escape_url_path(path: &str) -> String {
+	let mut escaped = String::with_capacity(path.len());
+	for c in path.chars() {
+		match c {
+			'%' => escaped.push_str("%25"),
+			' ' => escaped.push_str("%20"),
+			'#' => escaped.push_str("%23"),
+			'?' => escaped.push_str("%3F"),
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+// turns an SSH or HTTPS remote URL into https://host/owner/repo
+fn web_base_url(remote_url: &str) -> Option<String> {
+	let (host, path) = if let Some(rest) = remote_url
+		.strip_prefix("https://")
+		.or(remote_url.strip_prefix("http://"))
+	{
+		rest.split_once('/')?
+	} else if let Some(rest) = remote_url.strip_prefix("ssh://") {
+		let (host, path) = rest.split_once('/')?;
+		// drop the port, which is for SSH rather than the web UI
+		(host.split(':').next()?, path)
+	} else {
+		// scp-like syntax: git@host:owner/repo.git
+		remote_url.split_once(':')?
+	};
+	let host = host.rsplit('@').next()?;
+	let path = path.trim_end_matches('/');
+	let path = path.strip_suffix(".git").unwrap_or(path);
+	if host.is_empty() || !path.contains('/') {
+		return None;
+	}
+	Some(format!("https://{}/{}", host, path))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{path::Path, thread, time::Duration};
+
+	use git2::Oid;
+
+	use super::{
+		blame, commit_before, decode_blame_output, escape_url_path, expand_tabs, file_exists, fmt_width,
+		last_commit_with, line_count, map_line, parse_file_log, parse_ignore_revs, resolve_path, unambiguous_abbrev,
+		web_base_url, BlameOptions, BlameStream, FollowOutput, LogFollow, LogOptions,
+	};
+	use crate::{highlight::CodeSpan, temp_dir::TempDir};
+
+	#[test]
+	fn exists() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let head = repo.head().unwrap().target().unwrap();
+		assert!(file_exists(&repo, head, Path::new("src/main.rs")).unwrap());
+		assert!(!file_exists(&repo, head, Path::new("src/does_not_exist.rs")).unwrap());
+		assert_eq!(
+			last_commit_with(&repo, head, Path::new("src/main.rs")).unwrap(),
+			Some(head)
+		);
+		assert_eq!(
+			last_commit_with(&repo, head, Path::new("src/does_not_exist.rs")).unwrap(),
+			None
+		);
+
+		let lines = std::fs::read_to_string("src/git.rs").unwrap().lines().count();
+		assert_eq!(line_count(&repo, Oid::zero(), Path::new("src/git.rs")).unwrap(), lines);
+		assert!(line_count(&repo, head, Path::new("src/does_not_exist.rs")).is_err());
+	}
+
+	#[test]
+	fn file_log() {
+		let a = "1".repeat(40);
+		let b = "2".repeat(40);
+		let c = "3".repeat(40);
+		let output = format!(
+			"\x1e{a}\0Ann\01700000000\0rename it\n\nsrc/new.rs\n\x1e{b}\0Bob\01600000000\0merge\n\x1e{c}\0Ann\00\0add it\n\nsrc/old.rs\n"
+		);
+		let commits = parse_file_log(&output, Path::new("src/new.rs")).unwrap();
+		let summary: Vec<_> = commits
+			.iter()
+			.map(|c| {
+				(
+					c.commit.to_string(),
+					c.path.to_str().unwrap(),
+					c.author.as_str(),
+					c.summary.as_str(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			summary,
+			[
+				(a, "src/new.rs", "Ann", "rename it"),
+				(b, "src/new.rs", "Bob", "merge"),
+				(c, "src/old.rs", "Ann", "add it"),
+			]
+		);
+		assert_eq!(commits[2].time, std::time::UNIX_EPOCH);
+
+		let repo = git2::Repository::open_from_env().unwrap();
+		let commits = super::file_log(&repo, Path::new("src/main.rs"), Oid::zero(), false).unwrap();
+		assert!(!commits.is_empty());
+		assert!(commits.iter().all(|c| repo.find_commit(c.commit).is_ok()));
+	}
+
+	#[test]
+	fn blame_stream() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let head = repo.head().unwrap().peel_to_commit().unwrap().id();
+		let path = Path::new("src/terminal.rs");
+		for range in [None, Some((100, 200))] {
+			let options = BlameOptions {
+				range,
+				..BlameOptions::default()
+			};
+			let expected = blame(&repo, path, head, &options, None).unwrap();
+			let mut stream = BlameStream::spawn(&repo, path, head, &options, None).unwrap();
+			let mut streamed = vec![];
+			let result = loop {
+				match stream.poll(&mut streamed) {
+					Some(result) => break result,
+					None => thread::sleep(Duration::from_millis(10)),
+				}
+			};
+			assert!(result.is_ok());
+			// the same as all at once, including where git joined hunks
+			assert_eq!(streamed.len(), expected.len());
+			for (line, expected) in streamed.iter().zip(&expected) {
+				assert_eq!(
+					(line.commit, line.line_num, line.first_in_hunk, &line.author),
+					(
+						expected.commit,
+						expected.line_num,
+						expected.first_in_hunk,
+						&expected.author
+					)
+				);
+				assert_eq!(line.code, expected.code);
+			}
+		}
+	}
+
+	#[test]
+	fn map_lines() {
+		let old = b"a\nb\nc\nd\ne\n";
+		let new = b"new\na\nc\nC\nd\ne\n";
+		assert_eq!(map_line(old, old, 3).unwrap(), (3, true));
+		assert_eq!(map_line(old, new, 1).unwrap(), (2, true)); // after an insertion
+		assert_eq!(map_line(old, new, 2).unwrap(), (3, false)); // deleted
+		assert_eq!(map_line(old, new, 3).unwrap(), (3, true));
+		assert_eq!(map_line(old, new, 5).unwrap(), (6, true));
+	}
+
+	#[test]
+	fn before() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let head = repo.head().unwrap().peel_to_commit().unwrap();
+		let time = head.time().seconds();
+		assert_eq!(commit_before(&repo, head.id(), time + 1).unwrap(), Some(head.id()));
+		let before = commit_before(&repo, head.id(), time).unwrap();
+		assert_ne!(before, Some(head.id()));
+		assert!(before.is_none_or(|before| repo.find_commit(before).unwrap().time().seconds() < time));
+		assert_eq!(commit_before(&repo, head.id(), 0).unwrap(), None);
+	}
+
+	#[test]
+	fn abbrev() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let head = repo.head().unwrap().target().unwrap();
+		assert_eq!(unambiguous_abbrev(&repo, [head, Oid::zero()], 40), 40);
+		let abbrev = unambiguous_abbrev(&repo, [head], 1);
+		assert!(abbrev >= 4); // libgit2 says anything shorter is ambiguous
+		assert_eq!(
+			repo.find_object_by_prefix(&head.to_string()[..abbrev], None)
+				.unwrap()
+				.id(),
+			head
+		);
+	}
+
+	#[test]
+	fn follow() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let head = repo.head().unwrap().target().unwrap();
+		for first_parent in [false, true] {
+			let options = LogOptions {
+				first_parent,
+				..Default::default()
+			};
+			let history = LogFollow::spawn(&repo, Path::new("src/main.rs"), 0, 0, head, &options)
+				.unwrap()
+				.wait()
+				.unwrap();
+			assert!(String::from_utf8_lossy(&history)
+				.lines()
+				.next()
+				.unwrap()
+				.contains("commit"));
+		}
+		let options = LogOptions {
+			args: vec!["--format=whence %h".to_owned()],
+			..Default::default()
+		};
+		let history = LogFollow::spawn(&repo, Path::new("src/main.rs"), 0, 0, head, &options)
+			.unwrap()
+			.wait()
+			.unwrap();
+		assert!(history.starts_with(b"whence "));
+		// killed after the first chunk rather than waited for
+		let mut follow = LogFollow::spawn(&repo, Path::new("src/main.rs"), 0, 0, head, &LogOptions::default()).unwrap();
+		loop {
+			match follow.poll() {
+				FollowOutput::Pending => thread::sleep(Duration::from_millis(10)),
+				FollowOutput::More(history) => break assert!(!history.is_empty()),
+				FollowOutput::Done(_) => panic!("no history"),
+			}
+		}
+		drop(follow);
+	}
+
+	#[test]
+	fn unusual_entries() {
+		let dir = TempDir::new("entries");
+		let repo = git2::Repository::init(dir.path()).unwrap();
+		let blob = |content: &str| repo.blob(content.as_bytes()).unwrap();
+		let mut src = repo.treebuilder(None).unwrap();
+		src.insert("main.rs", blob("fn main() {}\n"), 0o100644).unwrap();
+		src.insert("link.rs", blob("main.rs"), 0o120000).unwrap();
+		let src = src.write().unwrap();
+		let mut root = repo.treebuilder(None).unwrap();
+		root.insert("src", src, 0o040000).unwrap();
+		root.insert("up", blob("src/link.rs"), 0o120000).unwrap();
+		root.insert("escape", blob("../elsewhere"), 0o120000).unwrap();
+		root.insert("loop", blob("loop"), 0o120000).unwrap();
+		root.insert("vendor", Oid::from_str("1234").unwrap(), 0o160000).unwrap();
+		let tree = repo.find_tree(root.write().unwrap()).unwrap();
+		let signature = git2::Signature::now("name", "name@example.com").unwrap();
+		let commit = repo
+			.commit(None, &signature, &signature, "entries", &tree, &[])
+			.unwrap();
+
+		let resolve = |path: &str| resolve_path(&repo, commit, Path::new(path));
+		assert_eq!(resolve("src/main.rs").unwrap(), Path::new("src/main.rs"));
+		assert_eq!(resolve("up").unwrap(), Path::new("src/main.rs"));
+		assert_eq!(resolve("missing.rs").unwrap(), Path::new("missing.rs"));
+		assert!(resolve("escape").unwrap_err().contains("outside the repository"));
+		assert!(resolve("loop").unwrap_err().contains("too many levels"));
+		assert!(resolve("vendor").unwrap_err().contains("submodule"));
+		assert!(resolve("src").unwrap_err().contains("directory"));
+	}
+
+	#[test]
+	fn worktree() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let path = Path::new("src/main.rs");
+		let blame = blame(&repo, path, Oid::zero(), &BlameOptions::default(), None).unwrap();
+		let on_disk = std::fs::read_to_string(repo.workdir().unwrap().join(path)).unwrap();
+		assert_eq!(blame.len(), on_disk.lines().count());
+	}
+
+	#[test]
+	fn tabs() {
+		let text = |spans: Vec<CodeSpan>| -> String { spans.iter().map(|span| span.text.as_str()).collect() };
+		assert_eq!(text(expand_tabs(vec![CodeSpan::plain("\tx")], 8)), "        x");
+		assert_eq!(text(expand_tabs(vec![CodeSpan::plain("ab\tx")], 4)), "ab  x");
+		// the tab stop carries across spans
+		let spans = vec![
+			CodeSpan::plain("abc"),
+			CodeSpan {
+				text: "\t".to_owned(),
+				color: Some((255, 0, 0)),
+			},
+			CodeSpan::plain("  \tx"),
+		];
+		assert_eq!(text(expand_tabs(spans, 4)), "abc     x");
+	}
+
+	#[test]
+	fn widths() {
+		assert_eq!(fmt_width("漢字", 5), "漢字 ");
+		assert_eq!(fmt_width("漢字", 3), "漢 ");
+		assert_eq!(fmt_width("e\u{301}t\u{e9}", 4), "e\u{301}t\u{e9} ");
+		let text = |spans: Vec<CodeSpan>| -> String { spans.iter().map(|span| span.text.as_str()).collect() };
+		assert_eq!(text(expand_tabs(vec![CodeSpan::plain("漢\tx")], 4)), "漢  x");
+	}
+
+	#[test]
+	fn latin1() {
+		let output = b"author Ren\xc3\xa9\n\tna\xefve\n\tplain\n";
+		let (decoded, encodings) = decode_blame_output(output);
+		assert_eq!(decoded, "author Ren\u{e9}\n\tna\u{ef}ve\n\tplain\n");
+		assert_eq!(encodings, [Some("windows-1252"), None]);
+	}
+
+	#[test]
+	fn ignore_revs() {
+		let contents = "# bulk reformat
+116aa62bf54a39697e25f21d6cf6799f7faa1349
+
+fa089b9b0b926c04e5d57812b7d7653472787965 # black
+116aa62b
+not a commit
+";
+		let (revs, warnings) = parse_ignore_revs(contents);
+		assert_eq!(
+			revs,
+			vec![
+				Oid::from_str("116aa62bf54a39697e25f21d6cf6799f7faa1349").unwrap(),
+				Oid::from_str("fa089b9b0b926c04e5d57812b7d7653472787965").unwrap(),
+			]
+		);
+		assert_eq!(
+			warnings,
+			vec![
+				"line 5: invalid object name 116aa62b",
+				"line 6: invalid object name not a commit"
+			]
+		);
+	}
+
+	#[test]
+	fn remote_urls() {
+		for url in [
+			"git@github.com:raylu/git-whence.git",
+			"ssh://git@github.com/raylu/git-whence.git",
+			"ssh://git@github.com:22/raylu/git-whence",
+			"https://github.com/raylu/git-whence.git",
+			"https://raylu@github.com/raylu/git-whence/",
+		] {
+			assert_eq!(
+				web_base_url(url).as_deref(),
+				Some("https://github.com/raylu/git-whence"),
+				"{}",
+				url
+			);
+		}
+		assert_eq!(
+			web_base_url("git@gitlab.com:group/subgroup/project.git").as_deref(),
+			Some("https://gitlab.com/group/subgroup/project")
+		);
+		assert_eq!(web_base_url("/srv/git/project.git"), None);
+		assert_eq!(escape_url_path("docs/a b#1.md"), "docs/a%20b%231.md");
 	}
 }
@@ -25,6 +25,8 @@ pub struct BlameLine<'a> {
 #[derive(Debug, PartialEq, Eq)]
 pub struct CommitInfo<'a> {
 	pub author: &'a str,
+	pub author_mail: &'a str,
+	pub summary: &'a str,
 	pub commit_time: SystemTime,
 	pub path: Option<&'a Path>,
 }
@@ -73,25 +75,68 @@ pub fn parse_blame_porcelain(input: &str) -> Result<Vec<BlameLine<'_>>, Box<dyn
 	Ok(hunks)
 }
 
+// an entry of git blame --incremental, which is a porcelain header without the code. the commit info only comes
+// with a commit's first entry, but every entry has its filename
+#[derive(Debug, PartialEq, Eq)]
+pub struct IncrementalEntry<'a> {
+	pub commit: &'a str,
+	pub orig_line_num: i32,
+	pub line_num: i32,
+	pub num_lines: i32,
+	pub info: Option<CommitInfo<'a>>,
+	pub path: &'a Path,
+}
+
+pub fn parse_incremental_entry(input: &str) -> Result<IncrementalEntry<'_>, Box<dyn error::Error>> {
+	let (remaining, header) = match parse_header(input) {
+		Ok(r) => r,
+		Err(e) => return Err(Box::new(e.map_input(|es| es.to_owned()))),
+	};
+	let mut info = CommitInfo {
+		author: "",
+		author_mail: "",
+		summary: "",
+		commit_time: SystemTime::UNIX_EPOCH,
+		path: None,
+	};
+	let mut has_info = false;
+	for line in remaining.lines() {
+		let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+		has_info |= field == "author";
+		set_commit_field(&mut info, field, value);
+	}
+	let path = info.path.ok_or("expected filename")?;
+	Ok(IncrementalEntry {
+		commit: header.commit,
+		orig_line_num: header.orig_line_no,
+		line_num: header.line_no,
+		num_lines: header.group_size,
+		info: has_info.then_some(info),
+		path,
+	})
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct Header<'a> {
 	commit: &'a str,
+	orig_line_no: i32,
 	line_no: i32,
 	group_size: i32,
 }
 
-fn parse_header(input: &str) -> IResult<&str, Header> {
+fn parse_header(input: &str) -> IResult<&str, Header<'_>> {
 	let space = tag(" ");
 	let commit = terminated(take_until1(" "), &space);
 	let orig_line = terminated(take_until1(" "), &space);
 	let final_line = take_while1(is_digit);
 	let group_size = opt(preceded(&space, take_while1(is_digit)));
-	let (remaining, (commit, _, final_line, group_size, _)) =
+	let (remaining, (commit, orig_line, final_line, group_size, _)) =
 		(commit, orig_line, final_line, group_size, line_ending).parse(input)?;
 	Ok((
 		remaining,
 		Header {
 			commit,
+			orig_line_no: orig_line.parse().unwrap(),
 			line_no: final_line.parse().unwrap(),
 			group_size: match group_size {
 				Some(b) => b.parse().unwrap(),
@@ -101,7 +146,7 @@ fn parse_header(input: &str) -> IResult<&str, Header> {
 	))
 }
 
-fn parse_commit_info(input: &str) -> IResult<&str, CommitInfo> {
+fn parse_commit_info(input: &str) -> IResult<&str, CommitInfo<'_>> {
 	let mut parse_line = (
 		terminated(take_until1(" "), tag(" ")),
 		terminated(take_till1(is_line_ending), line_ending),
@@ -109,6 +154,8 @@ fn parse_commit_info(input: &str) -> IResult<&str, CommitInfo> {
 
 	let mut ret = CommitInfo {
 		author: "",
+		author_mail: "",
+		summary: "",
 		commit_time: SystemTime::UNIX_EPOCH,
 		path: None,
 	};
@@ -121,21 +168,27 @@ fn parse_commit_info(input: &str) -> IResult<&str, CommitInfo> {
 			Err(_) => {
 				let (field, value);
 				(remaining, (field, value)) = parse_line.parse(remaining)?;
-				match field {
-					"author" => ret.author = value,
-					"committer-time" => {
-						let timestamp: u64 = value.parse().unwrap();
-						ret.commit_time = make_time(timestamp);
-					}
-					"filename" => ret.path = Some(Path::new(value)),
-					_ => {}
-				}
+				set_commit_field(&mut ret, field, value);
 			}
 		};
 	}
 	panic!("couldn't find code line starting with tab")
 }
 
+fn set_commit_field<'a>(info: &mut CommitInfo<'a>, field: &str, value: &'a str) {
+	match field {
+		"author" => info.author = value,
+		"author-mail" => info.author_mail = value.trim_start_matches('<').trim_end_matches('>'),
+		"summary" => info.summary = value,
+		"committer-time" => {
+			let timestamp: u64 = value.parse().unwrap();
+			info.commit_time = make_time(timestamp);
+		}
+		"filename" => info.path = Some(Path::new(value)),
+		_ => {}
+	}
+}
+
 #[inline]
 fn is_digit(c: char) -> bool {
 	c.is_dec_digit()
@@ -158,7 +211,9 @@ mod tests {
 		time::{self, SystemTime},
 	};
 
-	use crate::git_blame_porcelain::{make_time, parse_blame_porcelain, parse_header, BlameLine, CommitInfo, Header};
+	use crate::git_blame_porcelain::{
+		make_time, parse_blame_porcelain, parse_header, parse_incremental_entry, BlameLine, CommitInfo, Header,
+	};
 
 	use super::parse_commit_info;
 
@@ -180,6 +235,8 @@ mod tests {
 				],
 				info: Rc::new(CommitInfo {
 					author: "Georg Brandl",
+					author_mail: "georg@python.org",
+					summary: "Move the 3k reST doc tree in place.",
 					commit_time: make_time(1187188102),
 					path: Some(Path::new("Doc/library/gc.rst")),
 				}),
@@ -190,6 +247,8 @@ mod tests {
 				code: vec![""],
 				info: Rc::new(CommitInfo {
 					author: "Terry Jan Reedy",
+					author_mail: "tjreedy@udel.edu",
+					summary: "Issue #22558: Add remaining doc links to source code for Python-coded modules.",
 					commit_time: make_time(1465671774),
 					path: Some(Path::new("Doc/library/gc.rst")),
 				}),
@@ -204,6 +263,8 @@ mod tests {
 				],
 				info: Rc::new(CommitInfo {
 					author: "Georg Brandl",
+					author_mail: "georg@python.org",
+					summary: "Move the 3k reST doc tree in place.",
 					commit_time: make_time(1187188102),
 					path: Some(Path::new("Doc/library/gc.rst")),
 				}),
@@ -214,6 +275,8 @@ mod tests {
 				code: vec!["--------------"],
 				info: Rc::new(CommitInfo {
 					author: "Terry Jan Reedy",
+					author_mail: "tjreedy@udel.edu",
+					summary: "Issue #22558: Add remaining doc links to source code for Python-coded modules.",
 					commit_time: make_time(1465671774),
 					path: Some(Path::new("Doc/library/gc.rst")),
 				}),
@@ -232,12 +295,40 @@ mod tests {
 			result.1,
 			Header {
 				commit: "116aa62bf54a39697e25f21d6cf6799f7faa1349",
+				orig_line_no: 2,
 				line_no: 1,
 				group_size: 5,
 			}
 		);
 	}
 
+	#[test]
+	fn incremental_entry() {
+		let first = "116aa62bf54a39697e25f21d6cf6799f7faa1349 2 1 5
+author Georg Brandl
+author-mail <georg@python.org>
+author-time 1187188102
+author-tz +0000
+committer Georg Brandl
+committer-mail <georg@python.org>
+committer-time 1187188102
+committer-tz +0000
+summary Move the 3k reST doc tree in place.
+filename Doc/library/gc.rst
+";
+		let entry = parse_incremental_entry(first).expect("couldn't parse entry");
+		assert_eq!((entry.orig_line_num, entry.line_num, entry.num_lines), (2, 1, 5));
+		assert_eq!(entry.info.unwrap().author, "Georg Brandl");
+		assert_eq!(entry.path, Path::new("Doc/library/gc.rst"));
+
+		// the commit info isn't repeated
+		let later = "116aa62bf54a39697e25f21d6cf6799f7faa1349 8 7 3\nfilename Doc/library/gc.rst\n";
+		let entry = parse_incremental_entry(later).expect("couldn't parse entry");
+		assert_eq!(entry.commit, "116aa62bf54a39697e25f21d6cf6799f7faa1349");
+		assert_eq!(entry.info, None);
+		assert!(parse_incremental_entry("116aa62bf54a39697e25f21d6cf6799f7faa1349 8 7 3\n").is_err());
+	}
+
 	#[test]
 	fn commit_info() {
 		let data = "author raylu
@@ -259,6 +350,8 @@ filename Doc/library/gc.rst
 			commit_info,
 			CommitInfo {
 				author: "raylu",
+				author_mail: "mail@fake.tld",
+				summary: "blah blah",
 				commit_time: SystemTime::UNIX_EPOCH + time::Duration::from_secs(9876543210),
 				path: Some(Path::new("Doc/library/gc.rst")),
 			}
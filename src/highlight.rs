@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use syntect::{
+	easy::HighlightLines,
+	highlighting::{Theme, ThemeSet},
+	parsing::SyntaxSet,
+};
+
+// a piece of a line in one color, or the terminal's own color when it wasn't highlighted
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeSpan {
+	pub text: String,
+	pub color: Option<(u8, u8, u8)>,
+}
+
+impl CodeSpan {
+	pub fn plain(text: impl Into<String>) -> CodeSpan {
+		CodeSpan {
+			text: text.into(),
+			color: None,
+		}
+	}
+}
+
+pub struct Highlighter {
+	syntax_set: SyntaxSet,
+	theme: Theme,
+}
+
+impl Highlighter {
+	pub fn new() -> Highlighter {
+		let mut themes = ThemeSet::load_defaults().themes;
+		Highlighter {
+			syntax_set: SyntaxSet::load_defaults_newlines(),
+			theme: themes.remove("base16-ocean.dark").unwrap(),
+		}
+	}
+
+	// returns one Vec of spans per input line or None if the language isn't recognized. tabs are left for the caller
+	// to expand, since that depends on the column where each span starts
+	pub fn highlight(&self, path: &Path, lines: &[&str]) -> Option<Vec<Vec<CodeSpan>>> {
+		let syntax = match path.extension().and_then(|ext| ext.to_str()) {
+			Some(ext) => self.syntax_set.find_syntax_by_extension(ext),
+			None => None,
+		}
+		.or_else(|| self.syntax_set.find_syntax_by_first_line(lines.first()?))?;
+
+		let mut highlighter = HighlightLines::new(syntax, &self.theme);
+		let mut out = Vec::with_capacity(lines.len());
+		for line in lines {
+			// the newlines syntax set expects each line to end with \n
+			let line = format!("{}\n", line);
+			let ranges = highlighter.highlight_line(&line, &self.syntax_set).ok()?;
+			let spans = ranges
+				.into_iter()
+				.map(|(style, text)| CodeSpan {
+					text: text.trim_end_matches('\n').to_owned(),
+					color: Some((style.foreground.r, style.foreground.g, style.foreground.b)),
+				})
+				.collect();
+			out.push(spans);
+		}
+		Some(out)
+	}
+}
+
+impl Default for Highlighter {
+	fn default() -> Highlighter {
+		Highlighter::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::Path;
+
+	use super::Highlighter;
+
+	#[test]
+	fn highlight() {
+		let highlighter = Highlighter::new();
+		let lines = highlighter
+			.highlight(Path::new("src/main.rs"), &["fn main() {", "\tlet x = 1;", "}"])
+			.expect("rust should be recognized");
+		assert_eq!(lines.len(), 3);
+		let second: String = lines[1].iter().map(|span| span.text.as_str()).collect();
+		assert_eq!(second, "\tlet x = 1;");
+		assert!(lines[0].len() > 1);
+
+		assert!(highlighter
+			.highlight(Path::new("notes.unknownext"), &["hello"])
+			.is_none());
+	}
+}
@@ -0,0 +1,764 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::text::{Line, Text};
+
+use crate::config::KeyNames;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+	Help,
+	Quit,
+	ErrorLog,
+	ScrollDown,
+	ScrollUp,
+	PageDown,
+	PageUp,
+	Bottom,
+	Top,
+	ViewDown,
+	ViewUp,
+	NextCommit,
+	PrevCommit,
+	NextAuthor,
+	PrevAuthor,
+	SetMark,
+	JumpToMark,
+	GotoLine,
+	ScrollRight,
+	ScrollLeft,
+	SwitchPane,
+	Search,
+	SearchNext,
+	SearchPrev,
+	AuthorFilter,
+	ShowCommit,
+	CommitDetails,
+	LineHistory,
+	FileLog,
+	SelectRange,
+	FileDiff,
+	BlameParent,
+	BlameRev,
+	Reload,
+	FirstParent,
+	Quickfix,
+	Compare,
+	PopBlame,
+	BlameStack,
+	OpenFile,
+	CopyCommit,
+	CopyPermalink,
+	CopyBlame,
+	OpenCommit,
+	Edit,
+	IgnoreWhitespace,
+	DetectCopies,
+	Heatmap,
+	Dim,
+	AuthorStats,
+	LineNumbers,
+	Fold,
+	Dates,
+	Wrap,
+	Summary,
+	SummaryWidth,
+	AuthorBadges,
+	Layout,
+	Center,
+	Minimap,
+	SplitLeft,
+	SplitRight,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+	pub code: KeyCode,
+	pub modifiers: KeyModifiers,
+}
+
+impl Key {
+	const fn new(code: KeyCode) -> Key {
+		Key {
+			code,
+			modifiers: KeyModifiers::NONE,
+		}
+	}
+
+	const fn char(c: char) -> Key {
+		Key::new(KeyCode::Char(c))
+	}
+
+	const fn ctrl(c: char) -> Key {
+		Key {
+			code: KeyCode::Char(c),
+			modifiers: KeyModifiers::CONTROL,
+		}
+	}
+
+	fn matches(&self, event: &KeyEvent) -> bool {
+		// shift is already reflected in the case of the char
+		let modifiers = match event.code {
+			KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+			_ => event.modifiers,
+		};
+		self.code == event.code && self.modifiers == modifiers
+	}
+
+	fn display(&self) -> String {
+		let code = match self.code {
+			KeyCode::Char(c) => c.to_string(),
+			KeyCode::Down => "↓".to_owned(),
+			KeyCode::Up => "↑".to_owned(),
+			KeyCode::Right => "→".to_owned(),
+			KeyCode::Left => "←".to_owned(),
+			KeyCode::PageDown => "pgdown".to_owned(),
+			KeyCode::PageUp => "pgup".to_owned(),
+			KeyCode::Home => "home".to_owned(),
+			KeyCode::End => "end".to_owned(),
+			KeyCode::Enter => "enter".to_owned(),
+			KeyCode::Esc => "esc".to_owned(),
+			KeyCode::Tab => "tab".to_owned(),
+			KeyCode::Backspace => "backspace".to_owned(),
+			code => format!("{:?}", code).to_lowercase(),
+		};
+		if self.modifiers.contains(KeyModifiers::CONTROL) {
+			format!("ctrl-{}", code)
+		} else {
+			code
+		}
+	}
+}
+
+impl FromStr for Key {
+	type Err = String;
+
+	// parses names like j, G, ctrl-d, pgdown, and enter
+	fn from_str(s: &str) -> Result<Key, String> {
+		let (modifiers, name) = if let Some(name) = s.strip_prefix("ctrl-") {
+			(KeyModifiers::CONTROL, name)
+		} else if let Some(name) = s.strip_prefix("alt-") {
+			(KeyModifiers::ALT, name)
+		} else {
+			(KeyModifiers::NONE, s)
+		};
+		let mut chars = name.chars();
+		let code = match (chars.next(), chars.next()) {
+			(Some(c), None) => match c {
+				'↓' => KeyCode::Down,
+				'↑' => KeyCode::Up,
+				'→' => KeyCode::Right,
+				'←' => KeyCode::Left,
+				c => KeyCode::Char(c),
+			},
+			_ => match name.to_lowercase().as_str() {
+				"down" => KeyCode::Down,
+				"up" => KeyCode::Up,
+				"right" => KeyCode::Right,
+				"left" => KeyCode::Left,
+				"pgdown" | "pagedown" => KeyCode::PageDown,
+				"pgup" | "pageup" => KeyCode::PageUp,
+				"home" => KeyCode::Home,
+				"end" => KeyCode::End,
+				"enter" => KeyCode::Enter,
+				"esc" => KeyCode::Esc,
+				"tab" => KeyCode::Tab,
+				"backspace" => KeyCode::Backspace,
+				"space" => KeyCode::Char(' '),
+				_ => return Err(format!("unknown key {}", s)),
+			},
+		};
+		Ok(Key { code, modifiers })
+	}
+}
+
+struct ActionInfo {
+	action: Action,
+	name: &'static str, // used in the config file
+	section: &'static str,
+	description: &'static str,
+	keys: &'static [Key],
+}
+
+// in the order they're listed in the help
+const ACTIONS: &[ActionInfo] = &[
+	ActionInfo {
+		action: Action::Help,
+		name: "help",
+		section: "",
+		description: "this help",
+		keys: &[Key::char('h'), Key::char('?')],
+	},
+	ActionInfo {
+		action: Action::Quit,
+		name: "quit",
+		section: "",
+		description: "close window",
+		keys: &[Key::char('q'), Key::new(KeyCode::Esc)],
+	},
+	ActionInfo {
+		action: Action::ErrorLog,
+		name: "error_log",
+		section: "",
+		description: "show recent errors",
+		keys: &[Key::char('E')],
+	},
+	ActionInfo {
+		action: Action::ScrollDown,
+		name: "scroll_down",
+		section: "moving",
+		description: "down one line",
+		keys: &[Key::char('j'), Key::new(KeyCode::Down)],
+	},
+	ActionInfo {
+		action: Action::ScrollUp,
+		name: "scroll_up",
+		section: "moving",
+		description: "up one line",
+		keys: &[Key::char('k'), Key::new(KeyCode::Up)],
+	},
+	ActionInfo {
+		action: Action::PageDown,
+		name: "page_down",
+		section: "moving",
+		description: "down a page (half a window unless page_scroll says otherwise)",
+		keys: &[Key::char('d'), Key::new(KeyCode::PageDown)],
+	},
+	ActionInfo {
+		action: Action::PageUp,
+		name: "page_up",
+		section: "moving",
+		description: "up a page",
+		keys: &[Key::char('u'), Key::new(KeyCode::PageUp)],
+	},
+	ActionInfo {
+		action: Action::Bottom,
+		name: "bottom",
+		section: "moving",
+		description: "to last line",
+		keys: &[Key::char('G'), Key::new(KeyCode::End)],
+	},
+	ActionInfo {
+		action: Action::Top,
+		name: "top",
+		section: "moving",
+		description: "to first line",
+		keys: &[Key::char('g'), Key::new(KeyCode::Home)],
+	},
+	ActionInfo {
+		action: Action::ViewDown,
+		name: "view_down",
+		section: "moving",
+		description: "scroll the view down a row, keeping the selection if it's still visible",
+		keys: &[Key::ctrl('e')],
+	},
+	ActionInfo {
+		action: Action::ViewUp,
+		name: "view_up",
+		section: "moving",
+		description: "scroll the view up a row",
+		keys: &[Key::ctrl('y')],
+	},
+	ActionInfo {
+		action: Action::NextCommit,
+		name: "next_commit",
+		section: "moving",
+		description: "go to the first line of the next run of lines from one commit",
+		keys: &[Key::char(']')],
+	},
+	ActionInfo {
+		action: Action::PrevCommit,
+		name: "prev_commit",
+		section: "moving",
+		description: "go to the start of this run of lines, or the previous one",
+		keys: &[Key::char('[')],
+	},
+	ActionInfo {
+		action: Action::NextAuthor,
+		name: "next_author",
+		section: "moving",
+		description: "go to the next line by a different author, skipping their other commits",
+		keys: &[Key::char('}')],
+	},
+	ActionInfo {
+		action: Action::PrevAuthor,
+		name: "prev_author",
+		section: "moving",
+		description: "go to the start of this author's run of lines, or the previous author's",
+		keys: &[Key::char('{')],
+	},
+	ActionInfo {
+		action: Action::SetMark,
+		name: "set_mark",
+		section: "moving",
+		description: "mark the selected line with the next letter typed",
+		keys: &[Key::char('m')],
+	},
+	ActionInfo {
+		action: Action::JumpToMark,
+		name: "jump_to_mark",
+		section: "moving",
+		description: "go to the line marked with the next letter typed, or ' for before the last jump",
+		keys: &[Key::char('\'')],
+	},
+	ActionInfo {
+		action: Action::GotoLine,
+		name: "goto_line",
+		section: "moving",
+		description: "to a line number, e.g. :123",
+		keys: &[Key::char(':')],
+	},
+	ActionInfo {
+		action: Action::ScrollRight,
+		name: "scroll_right",
+		section: "moving",
+		description: "scroll code right",
+		keys: &[Key::new(KeyCode::Right)],
+	},
+	ActionInfo {
+		action: Action::ScrollLeft,
+		name: "scroll_left",
+		section: "moving",
+		description: "scroll code left",
+		keys: &[Key::new(KeyCode::Left)],
+	},
+	ActionInfo {
+		action: Action::SwitchPane,
+		name: "switch_pane",
+		section: "moving",
+		description: "move between the blame and the comparison",
+		keys: &[Key::new(KeyCode::Tab)],
+	},
+	ActionInfo {
+		action: Action::Search,
+		name: "search",
+		section: "search",
+		description: "search, or search the right panel when open (ignores case unless the query has uppercase)",
+		keys: &[Key::char('/')],
+	},
+	ActionInfo {
+		action: Action::SearchNext,
+		name: "search_next",
+		section: "search",
+		description: "repeat search forward, wrapping at the end",
+		keys: &[Key::char('n')],
+	},
+	ActionInfo {
+		action: Action::SearchPrev,
+		name: "search_prev",
+		section: "search",
+		description: "repeat search backward, wrapping at the start",
+		keys: &[Key::char('N')],
+	},
+	ActionInfo {
+		action: Action::AuthorFilter,
+		name: "author_filter",
+		section: "search",
+		description: "only show lines by an author (esc clears)",
+		keys: &[Key::char('A')],
+	},
+	ActionInfo {
+		action: Action::ShowCommit,
+		name: "show_commit",
+		section: "git",
+		description: "show commit, or expand a fold",
+		keys: &[Key::new(KeyCode::Enter)],
+	},
+	ActionInfo {
+		action: Action::CommitDetails,
+		name: "commit_details",
+		section: "git",
+		description: "toggle commit details",
+		keys: &[Key::char('i')],
+	},
+	ActionInfo {
+		action: Action::LineHistory,
+		name: "line_history",
+		section: "git",
+		description: "trace line or selected lines through history (git -L)",
+		keys: &[Key::char('w')],
+	},
+	ActionInfo {
+		action: Action::FileLog,
+		name: "file_log",
+		section: "git",
+		description: "list the commits that changed the file (git log --follow), to blame at one of them",
+		keys: &[Key::char('H')],
+	},
+	ActionInfo {
+		action: Action::SelectRange,
+		name: "select_range",
+		section: "git",
+		description: "start or cancel selecting lines for line history",
+		keys: &[Key::char('v')],
+	},
+	ActionInfo {
+		action: Action::FileDiff,
+		name: "file_diff",
+		section: "git",
+		description: "show commit's diff for this file",
+		keys: &[Key::char('f')],
+	},
+	ActionInfo {
+		action: Action::BlameParent,
+		name: "blame_parent",
+		section: "git",
+		description: "reblame line at parent commit (prompts for which parent of a merge)",
+		keys: &[Key::char('b')],
+	},
+	ActionInfo {
+		action: Action::BlameRev,
+		name: "blame_rev",
+		section: "git",
+		description: "blame at a branch, tag, or commit",
+		keys: &[Key::char('R')],
+	},
+	ActionInfo {
+		action: Action::Reload,
+		name: "reload",
+		section: "git",
+		description: "blame again, at the new HEAD if it's moved since starting there",
+		keys: &[Key::ctrl('r')],
+	},
+	ActionInfo {
+		action: Action::Quickfix,
+		name: "quickfix",
+		section: "git",
+		description: "write the filtered author's lines, or the selected line author's, as path:line: code",
+		keys: &[Key::char('Q')],
+	},
+	ActionInfo {
+		action: Action::FirstParent,
+		name: "first_parent",
+		section: "git",
+		description: "toggle following only first parents in line history",
+		keys: &[Key::char('P')],
+	},
+	ActionInfo {
+		action: Action::Compare,
+		name: "compare",
+		section: "git",
+		description: "blame at another revision side by side, or close the comparison",
+		keys: &[Key::char('D')],
+	},
+	ActionInfo {
+		action: Action::PopBlame,
+		name: "pop_blame",
+		section: "git",
+		description: "undo/pop blame stack",
+		keys: &[Key::char('B')],
+	},
+	ActionInfo {
+		action: Action::BlameStack,
+		name: "blame_stack",
+		section: "git",
+		description: "list the blames `b` went through, to go back to any of them",
+		keys: &[Key::char('S')],
+	},
+	ActionInfo {
+		action: Action::OpenFile,
+		name: "open_file",
+		section: "git",
+		description: "blame another file at this commit",
+		keys: &[Key::char('e')],
+	},
+	ActionInfo {
+		action: Action::CopyCommit,
+		name: "copy_commit",
+		section: "git",
+		description: "copy commit hash",
+		keys: &[Key::char('y')],
+	},
+	ActionInfo {
+		action: Action::CopyPermalink,
+		name: "copy_permalink",
+		section: "git",
+		description: "copy a web link to the selected line",
+		keys: &[Key::char('Y')],
+	},
+	ActionInfo {
+		action: Action::CopyBlame,
+		name: "copy_blame",
+		section: "git",
+		description: "copy the blame as shown, skipping filtered out lines",
+		keys: &[Key::char('X')],
+	},
+	ActionInfo {
+		action: Action::OpenCommit,
+		name: "open_commit",
+		section: "git",
+		description: "open commit in browser (origin remote)",
+		keys: &[Key::char('o')],
+	},
+	ActionInfo {
+		action: Action::Edit,
+		name: "edit",
+		section: "git",
+		description: "open the selected line in $VISUAL or $EDITOR",
+		keys: &[Key::char('O')],
+	},
+	ActionInfo {
+		action: Action::IgnoreWhitespace,
+		name: "ignore_whitespace",
+		section: "view",
+		description: "toggle ignoring whitespace changes (git blame -w)",
+		keys: &[Key::char('W')],
+	},
+	ActionInfo {
+		action: Action::DetectCopies,
+		name: "detect_copies",
+		section: "view",
+		description: "toggle finding lines copied from other files (git blame -CCC, slow)",
+		keys: &[Key::char('p')],
+	},
+	ActionInfo {
+		action: Action::Heatmap,
+		name: "heatmap",
+		section: "view",
+		description: "toggle coloring lines by commit age",
+		keys: &[Key::char('a')],
+	},
+	ActionInfo {
+		action: Action::Dim,
+		name: "dim",
+		section: "view",
+		description: "toggle dimming lines older (or newer) than [dim] days",
+		keys: &[Key::char('T')],
+	},
+	ActionInfo {
+		action: Action::AuthorStats,
+		name: "author_stats",
+		section: "view",
+		description: "toggle lines per author",
+		keys: &[Key::char('s')],
+	},
+	ActionInfo {
+		action: Action::LineNumbers,
+		name: "line_numbers",
+		section: "view",
+		description: "toggle line numbers",
+		keys: &[Key::char('#')],
+	},
+	ActionInfo {
+		action: Action::Fold,
+		name: "fold",
+		section: "view",
+		description: "fold runs of lines from the same commit",
+		keys: &[Key::char('z')],
+	},
+	ActionInfo {
+		action: Action::Dates,
+		name: "dates",
+		section: "view",
+		description: "toggle relative/absolute dates",
+		keys: &[Key::char('t')],
+	},
+	ActionInfo {
+		action: Action::Wrap,
+		name: "wrap",
+		section: "view",
+		description: "toggle wrapping long lines",
+		keys: &[Key::char('r')],
+	},
+	ActionInfo {
+		action: Action::Summary,
+		name: "summary",
+		section: "view",
+		description: "toggle commit summary column",
+		keys: &[Key::char('c')],
+	},
+	ActionInfo {
+		action: Action::SummaryWidth,
+		name: "summary_width",
+		section: "view",
+		description: "cycle commit summary column width",
+		keys: &[Key::char('C')],
+	},
+	ActionInfo {
+		action: Action::AuthorBadges,
+		name: "author_badges",
+		section: "view",
+		description: "toggle author initials colored by author",
+		keys: &[Key::char('I')],
+	},
+	ActionInfo {
+		action: Action::Layout,
+		name: "layout",
+		section: "view",
+		description: "cycle through the column layouts (minimal, author and date, full, or the config's)",
+		keys: &[Key::char('L')],
+	},
+	ActionInfo {
+		action: Action::Center,
+		name: "center",
+		section: "view",
+		description: "toggle keeping the selection centered",
+		keys: &[Key::char('Z')],
+	},
+	ActionInfo {
+		action: Action::Minimap,
+		name: "minimap",
+		section: "view",
+		description: "toggle a bar beside the blame showing each line's author and where commits change",
+		keys: &[Key::char('M')],
+	},
+	ActionInfo {
+		action: Action::SplitLeft,
+		name: "split_left",
+		section: "view",
+		description: "move the split between the blame and the right pane left",
+		keys: &[Key::char('<')],
+	},
+	ActionInfo {
+		action: Action::SplitRight,
+		name: "split_right",
+		section: "view",
+		description: "move the split between the blame and the right pane right",
+		keys: &[Key::char('>')],
+	},
+];
+
+pub struct Keymap {
+	bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+	fn default() -> Keymap {
+		Keymap {
+			bindings: ACTIONS.iter().map(|info| (info.action, info.keys.to_vec())).collect(),
+		}
+	}
+}
+
+impl Keymap {
+	// keys assigned in the config are removed from the actions they're bound to by default
+	pub fn with_overrides(overrides: &HashMap<String, KeyNames>) -> Result<Keymap, String> {
+		let mut keymap = Keymap::default();
+		let mut overridden = vec![];
+		for (name, names) in overrides {
+			let index = ACTIONS
+				.iter()
+				.position(|info| info.name == name)
+				.ok_or_else(|| format!("unknown action {}", name))?;
+			let keys = names
+				.names()
+				.iter()
+				.map(|name| name.parse())
+				.collect::<Result<Vec<Key>, _>>()?;
+			keymap.bindings[index].1 = keys;
+			overridden.push(index);
+		}
+		let taken: Vec<Key> = overridden.iter().flat_map(|&i| keymap.bindings[i].1.clone()).collect();
+		for (i, (_, keys)) in keymap.bindings.iter_mut().enumerate() {
+			if !overridden.contains(&i) {
+				keys.retain(|key| !taken.contains(key));
+			}
+		}
+		Ok(keymap)
+	}
+
+	pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+		self.bindings
+			.iter()
+			.find(|(_, keys)| keys.iter().any(|key| key.matches(event)))
+			.map(|(action, _)| *action)
+	}
+
+	pub fn help_text(&self) -> Text<'static> {
+		let mut lines = vec![];
+		let mut section = "";
+		for (info, (_, keys)) in std::iter::zip(ACTIONS, &self.bindings) {
+			if info.section != section {
+				section = info.section;
+				lines.extend([Line::default(), Line::from(format!("    {}", section)), Line::default()]);
+			}
+			let keys: Vec<String> = keys.iter().map(Key::display).collect();
+			lines.push(Line::from(format!("{:11} {}", keys.join("  "), info.description)));
+		}
+		lines.into()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+	use super::{Action, Key, Keymap, ACTIONS};
+	use crate::config::KeyNames;
+
+	#[test]
+	fn sections() {
+		// help_text starts a new header whenever the section changes, so each one has to be together
+		let mut seen: Vec<&str> = vec![];
+		for info in ACTIONS {
+			if seen.last() != Some(&info.section) {
+				assert!(!seen.contains(&info.section), "{} is out of its section", info.name);
+				seen.push(info.section);
+			}
+		}
+	}
+
+	#[test]
+	fn default_keymap() {
+		let keymap = Keymap::default();
+		let event = |code, modifiers| KeyEvent::new(code, modifiers);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('j'), KeyModifiers::NONE)),
+			Some(Action::ScrollDown)
+		);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+			Some(Action::Bottom)
+		);
+		assert_eq!(keymap.action(&event(KeyCode::Char('j'), KeyModifiers::CONTROL)), None);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('?'), KeyModifiers::NONE)),
+			Some(Action::Help)
+		);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Home, KeyModifiers::NONE)),
+			Some(Action::Top)
+		);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('g'), KeyModifiers::NONE)),
+			Some(Action::Top)
+		);
+		assert_eq!(
+			keymap.action(&event(KeyCode::End, KeyModifiers::NONE)),
+			Some(Action::Bottom)
+		);
+	}
+
+	#[test]
+	fn overrides() {
+		let overrides = HashMap::from([
+			("blame_parent".to_owned(), KeyNames::One("j".to_owned())),
+			(
+				"scroll_down".to_owned(),
+				KeyNames::Many(vec!["ctrl-n".to_owned(), "pgdown".to_owned()]),
+			),
+		]);
+		let keymap = Keymap::with_overrides(&overrides).unwrap();
+		let event = |code, modifiers| KeyEvent::new(code, modifiers);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('j'), KeyModifiers::NONE)),
+			Some(Action::BlameParent)
+		);
+		assert_eq!(
+			keymap.action(&event(KeyCode::Char('n'), KeyModifiers::CONTROL)),
+			Some(Action::ScrollDown)
+		);
+		// pgdown moved from page_down to scroll_down
+		assert_eq!(
+			keymap.action(&event(KeyCode::PageDown, KeyModifiers::NONE)),
+			Some(Action::ScrollDown)
+		);
+		assert_eq!(keymap.action(&event(KeyCode::Char('b'), KeyModifiers::NONE)), None);
+
+		let bad_action = HashMap::from([("explode".to_owned(), KeyNames::One("x".to_owned()))]);
+		assert!(Keymap::with_overrides(&bad_action).is_err());
+		assert!("ctrl-nope".parse::<Key>().is_err());
+	}
+}
@@ -0,0 +1,8 @@
+// blaming without the TUI: git::blame, git::log_follow, and formatting the result for other programs
+pub mod git;
+mod git_blame_porcelain;
+pub mod highlight;
+pub mod print;
+pub mod stats;
+#[cfg(test)]
+mod temp_dir;
@@ -1,44 +1,615 @@
 use git2::Repository;
 use std::{
-	env,
+	env, fs,
+	io::{self, Write},
 	path::{Path, PathBuf},
+	process,
+	sync::Arc,
 };
 
-mod git;
-mod git_blame_porcelain;
+use git_whence::{git, highlight, print, stats};
+
+mod config;
+mod keys;
+mod prefs;
+mod render;
+#[cfg(test)]
+mod temp_dir;
 mod terminal;
+mod theme;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Args {
+	path: String,
+	repo: Option<String>, // like git -C, so path is relative to it
+	rev: Option<String>,
+	as_of: Option<chrono::NaiveDate>, // blame the last commit in rev's first-parent history from that day or before
+	line: Option<usize>,
+	highlight: bool,
+	ignore_revs_file: Option<String>,
+	ignore_revs: Vec<String>, // --ignore-rev, added to the file's
+	range: Option<(usize, usize)>,
+	tab_width: Option<usize>,
+	abbrev: Option<usize>, // hex digits of shas, overriding the config and core.abbrev
+	page_scroll: Option<config::PageScroll>, // overrides the config
+	ignore_whitespace: bool, // overrides the saved preference for this run
+	worktree: bool,        // blame the file as it is on disk, including uncommitted changes
+	first_parent: bool,    // start with line history following first parents only
+	print: bool,           // write the blame to stdout instead of starting the TUI
+	format: print::Format,
+	author: Option<String>, // only print lines by authors matching this, like the `A` filter
+	summary: bool,          // follow the printed blame with line, commit, and author counts
+	force: bool,            // blame files longer than max_lines anyway
+	help: bool,             // print HELP and exit, ignoring the other arguments
+	version: bool,          // likewise for the version
+}
+
+// exit statuses, so scripts can tell failures apart
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NOT_A_REPO: i32 = 3;
+const EXIT_BAD_REV: i32 = 4;
+const EXIT_NO_FILE: i32 = 5;
+const EXIT_TOO_LONG: i32 = 6;
+
+// lockfiles and generated code are slow to blame and rarely worth it
+const DEFAULT_MAX_LINES: usize = 20_000;
+
+// the revision to blame at when the arguments don't give one
+const REV_VAR: &str = "GIT_WHENCE_REV";
+
+const USAGE: &str = "usage: git-whence [options] <filepath>[:line] [rev]";
+
+const HELP: &str = "interactively blame a file, reblaming at older versions and following lines through history
+
+arguments:
+  <filepath>[:line]         file to blame, relative to the current directory (or --repo), optionally with a line to select
+  [rev]                     branch, tag, or commit to blame at instead of $GIT_WHENCE_REV or HEAD
+
+options:
+      --rev <rev>           same as [rev]
+      --as-of <YYYY-MM-DD>  blame the file as it was at the end of that day, at the last commit committed by then
+                            on rev's (or HEAD's) first-parent history
+      --line <n>            select line n
+  -L, --range <start,end>   only blame lines start through end, like git blame -L
+      --repo <path>         blame in the repository at path, like git -C. in a bare repository, filepath is from
+                            the top of the tree and there's no HEAD to default to, so a rev is needed
+      --worktree            blame the file as it is on disk, with uncommitted lines marked as such
+      --ignore-revs-file <path>
+                            ignore the commits listed in path, like git blame --ignore-revs-file
+      --ignore-rev <rev>    ignore rev too, like git blame --ignore-rev; can be given more than once
+  -w, --ignore-whitespace   ignore whitespace changes, like git blame -w
+      --first-parent        follow line history through first parents only, like git log --first-parent
+      --no-highlight        don't syntax highlight the code
+      --force               blame the file even if it's longer than max_lines in the config (20000 by default)
+      --tabwidth <n>        expand tabs to every nth column (8 by default)
+      --page-scroll <n>     how far page down and up move: half (the default) or full a screen, or n lines
+      --abbrev <n>          show at least n hex digits of shas (core.abbrev or 7 by default), more when needed
+                            to be unambiguous
+      --print, --no-tui     write the blame to stdout instead of starting the TUI
+      --format <format>     columns to print: short (the default), long, full, json, or quickfix; implies --print
+      --json                same as --format json
+      --quickfix            same as --format quickfix, printing path:line: code for an editor's error list
+      --author <pattern>    only print lines whose \"name <email>\" contains pattern (smartcase); implies --print
+      --summary             after the blame, print how many lines, commits, and authors it has, the range of commit
+                            dates, and each author's lines; implies --print
+  -h, --help                print this help
+  -V, --version             print the version
+
+environment:
+  GIT_WHENCE_REV            the revision to blame at when none is given, instead of HEAD
+
+exit status:
+  0   success
+  1   any other error
+  2   invalid arguments
+  3   not in a git repository
+  4   a revision (the one to blame at, or --ignore-rev) wasn't found, or no commit is as old as --as-of
+  5   the file isn't in the repository at that revision or the 10000 before it, or isn't in the working tree for
+      --worktree
+  6   the file is longer than max_lines, without --force
+
+press h in the TUI for its keys";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+	let mut positional = vec![];
+	let mut parsed = Args {
+		highlight: true,
+		..Default::default()
+	};
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"-h" | "--help" => {
+				return Ok(Args {
+					help: true,
+					..Default::default()
+				})
+			}
+			"-V" | "--version" => {
+				return Ok(Args {
+					version: true,
+					..Default::default()
+				})
+			}
+			"--no-highlight" => parsed.highlight = false,
+			"--force" => parsed.force = true,
+			"-w" | "--ignore-whitespace" => parsed.ignore_whitespace = true,
+			"--ignore-revs-file" => {
+				parsed.ignore_revs_file = Some(args.next().ok_or("--ignore-revs-file needs a path")?)
+			}
+			"--ignore-rev" => parsed
+				.ignore_revs
+				.push(args.next().ok_or("--ignore-rev needs a revision")?),
+			"--repo" => parsed.repo = Some(args.next().ok_or("--repo needs a path")?),
+			"--rev" => parsed.rev = Some(args.next().ok_or("--rev needs a revision")?),
+			"--as-of" => {
+				let date = args.next().ok_or("--as-of needs a date")?;
+				parsed.as_of = Some(
+					chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+						.map_err(|_| format!("invalid date {} (expected YYYY-MM-DD)", date))?,
+				);
+			}
+			"--worktree" => parsed.worktree = true,
+			"--first-parent" => parsed.first_parent = true,
+			"--print" | "--no-tui" => parsed.print = true,
+			"--author" => {
+				parsed.author = Some(args.next().ok_or("--author needs a name or email")?);
+				parsed.print = true;
+			}
+			"--summary" => {
+				parsed.summary = true;
+				parsed.print = true;
+			}
+			"--quickfix" => {
+				parsed.format = print::Format::Quickfix;
+				parsed.print = true;
+			}
+			"--json" => {
+				parsed.format = print::Format::Json;
+				parsed.print = true;
+			}
+			"--format" => {
+				let format = args.next().ok_or("--format needs a format")?;
+				parsed.format = format.parse()?;
+				parsed.print = true;
+			}
+			"--range" | "-L" => parsed.range = Some(parse_range(&args.next().ok_or("--range needs START,END")?)?),
+			_ if arg.starts_with("-L") => parsed.range = Some(parse_range(&arg[2..])?),
+			"--tabwidth" => {
+				let width = args.next().ok_or("--tabwidth needs a width")?;
+				match width.parse() {
+					Ok(width) if width > 0 => parsed.tab_width = Some(width),
+					_ => return Err(format!("invalid tab width {}", width)),
+				}
+			}
+			"--page-scroll" => {
+				let page_scroll = args
+					.next()
+					.ok_or("--page-scroll needs half, full, or a number of lines")?;
+				parsed.page_scroll = Some(page_scroll.parse()?);
+			}
+			"--abbrev" => {
+				let abbrev = args.next().ok_or("--abbrev needs a length")?;
+				match abbrev.parse() {
+					Ok(abbrev @ 4..=40) => parsed.abbrev = Some(abbrev),
+					_ => return Err(format!("invalid abbrev {} (expected 4 to 40)", abbrev)),
+				}
+			}
+			"--line" => {
+				let line = args.next().ok_or("--line needs a line number")?;
+				parsed.line = Some(line.parse().map_err(|_| format!("invalid line number {}", line))?);
+			}
+			_ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+			_ => positional.push(arg),
+		}
+	}
+	let mut positional = positional.into_iter();
+	parsed.path = positional.next().ok_or("missing filepath")?;
+	if let Some(rev) = positional.next() {
+		if parsed.rev.is_some() {
+			return Err("give the revision either with --rev or after the filepath".to_owned());
+		}
+		parsed.rev = Some(rev);
+	}
+	if parsed.summary && matches!(parsed.format, print::Format::Json | print::Format::Quickfix) {
+		return Err("--summary only goes with the short, long, and full formats".to_owned());
+	}
+	if parsed.worktree && parsed.rev.is_some() {
+		return Err("--worktree already says what to blame, so it can't be given a revision".to_owned());
+	}
+	if parsed.worktree && parsed.as_of.is_some() {
+		return Err("--worktree already says what to blame, so it can't be given a date".to_owned());
+	}
+	if positional.next().is_some() {
+		return Err("too many arguments".to_owned());
+	}
+
+	// accept path:123 like grep and editors output, unless that's the actual filename
+	if let Some((path, line)) = parsed.path.rsplit_once(':') {
+		if let Ok(line) = line.parse() {
+			if parsed.line.is_none() && !Path::new(&parsed.path).exists() {
+				parsed.line = Some(line);
+				parsed.path = path.to_owned();
+			}
+		}
+	}
+	Ok(parsed)
+}
+
+// START,END like git blame -L
+fn parse_range(range: &str) -> Result<(usize, usize), String> {
+	let invalid = || format!("invalid range {} (expected START,END)", range);
+	let (start, end) = range.split_once(',').ok_or_else(invalid)?;
+	let start: usize = start.parse().map_err(|_| invalid())?;
+	let end: usize = end.parse().map_err(|_| invalid())?;
+	if start == 0 || end < start {
+		return Err(invalid());
+	}
+	Ok((start, end))
+}
+
+// fills in the revision from REV_VAR unless the arguments gave one or asked for the working tree
+fn default_rev(args: &mut Args, var: Option<String>) {
+	if args.rev.is_none() && !args.worktree {
+		args.rev = var.filter(|rev| !rev.is_empty());
+	}
+}
 
 fn main() {
-	let args: Vec<String> = env::args().collect();
-	if args.len() < 2 || args.len() > 3 {
-		println!("usage: {} <filepath> [rev]", args[0].rsplit('/').next().unwrap());
+	let mut args = match parse_args(env::args().skip(1)) {
+		Ok(args) => args,
+		Err(e) => {
+			eprintln!("{}\n{}\n\nrun git-whence --help for the options", e, USAGE);
+			process::exit(EXIT_USAGE);
+		}
+	};
+	if args.help {
+		println!("{}\n\n{}", USAGE, HELP);
 		return;
 	}
+	if args.version {
+		println!("git-whence {}", env!("CARGO_PKG_VERSION"));
+		return;
+	}
+	default_rev(&mut args, env::var(REV_VAR).ok());
 
-	let path = Path::new(&args[1]);
-	let (repo, abs_path) = match path.canonicalize() {
-		Ok(abs_path) => (Repository::discover(&abs_path).unwrap(), abs_path),
-		Err(_) => (Repository::open_from_env().unwrap(), path_to_lexical_absolute(path)),
+	let config = match config::load() {
+		Ok(config) => config,
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(EXIT_ERROR);
+		}
+	};
+	let keymap = match keys::Keymap::with_overrides(&config.keys) {
+		Ok(keymap) => keymap,
+		Err(e) => {
+			eprintln!("{}: {}", config::path().unwrap().display(), e);
+			process::exit(EXIT_ERROR);
+		}
+	};
+	let theme = match theme::Theme::with_overrides(&config.theme) {
+		Ok(theme) => theme,
+		Err(e) => {
+			eprintln!("{}: {}", config::path().unwrap().display(), e);
+			process::exit(EXIT_ERROR);
+		}
 	};
-	let rel_path = abs_path.strip_prefix(repo.workdir().unwrap()).unwrap().to_owned();
 
-	let commit = if args.len() == 3 {
-		repo.revparse_single(&args[2]).unwrap().id()
-	} else {
-		repo.head().unwrap().target().unwrap()
+	let (repo, rel_path) = match find_repo(&args.path, args.repo.as_deref()) {
+		Ok(found) => found,
+		Err((code, e)) => {
+			eprintln!("{}", e);
+			process::exit(code);
+		}
 	};
+
+	if repo.is_bare() && (args.rev.is_none() || args.worktree) {
+		eprintln!(
+			"{} is a bare repository, so there's no working tree; give a revision to blame at, like --rev HEAD",
+			repo.path().display()
+		);
+		process::exit(EXIT_USAGE);
+	}
+
+	// branches, tags, and short shas all peel to a commit
+	let commit = match &args.rev {
+		_ if args.worktree => Ok(git2::Oid::zero()),
+		Some(rev) => repo
+			.revparse_single(rev)
+			.and_then(|object| object.peel_to_commit())
+			.map(|commit| commit.id()),
+		None => repo
+			.head()
+			.and_then(|head| head.peel_to_commit())
+			.map(|commit| commit.id()),
+	};
+	let commit = match commit {
+		Ok(commit) => commit,
+		Err(e) => {
+			eprintln!("{}: {}", args.rev.as_deref().unwrap_or("HEAD"), e.message());
+			process::exit(EXIT_BAD_REV);
+		}
+	};
+	let abbrev = args
+		.abbrev
+		.or(config.abbrev.map(|abbrev| abbrev.clamp(4, 40)))
+		.unwrap_or_else(|| git::configured_abbrev(&repo));
+	let short = |commit| format!("{:.*}", git::unambiguous_abbrev(&repo, [commit], abbrev), commit);
+	let mut message = None;
+	let commit = match args.as_of {
+		Some(date) => {
+			// the end of the day, in local time
+			let cutoff = date
+				.succ_opt()
+				.and_then(|day| day.and_hms_opt(0, 0, 0))
+				.and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+				.map_or(i64::MAX, |midnight| midnight.timestamp());
+			match git::commit_before(&repo, commit, cutoff) {
+				Ok(Some(before)) => {
+					message = Some(format!("blaming {}, the last commit as of {}", short(before), date));
+					before
+				}
+				Ok(None) => {
+					eprintln!(
+						"no commit on {} is from {} or before",
+						args.rev.as_deref().unwrap_or("HEAD"),
+						date
+					);
+					process::exit(EXIT_BAD_REV);
+				}
+				Err(e) => {
+					eprintln!("{}", e.message());
+					process::exit(EXIT_ERROR);
+				}
+			}
+		}
+		None => commit,
+	};
+	// a deleted file is blamed where it last existed
+	let commit = match git::file_exists(&repo, commit, &rel_path) {
+		Ok(false) => match git::last_commit_with(&repo, commit, &rel_path) {
+			Ok(Some(last)) => {
+				message = Some(format!(
+					"{} doesn't exist at {}, blaming {}, the last commit that had it",
+					rel_path.display(),
+					args.rev.as_deref().unwrap_or("HEAD"),
+					short(last)
+				));
+				last
+			}
+			Ok(None) => {
+				eprintln!(
+					"{} doesn't exist at {} or the {} commits before it",
+					rel_path.display(),
+					args.rev.as_deref().unwrap_or("HEAD"),
+					git::LAST_COMMIT_MAX_WALK
+				);
+				process::exit(EXIT_NO_FILE);
+			}
+			Err(e) => {
+				eprintln!("{}", e.message());
+				process::exit(EXIT_ERROR);
+			}
+		},
+		_ => commit, // the working tree, or an error left for the blame to report
+	};
+	if args.worktree && !repo.workdir().unwrap().join(&rel_path).exists() {
+		eprintln!("{} doesn't exist in the working tree", rel_path.display());
+		process::exit(EXIT_NO_FILE);
+	}
+	let rel_path = match git::resolve_path(&repo, commit, &rel_path) {
+		Ok(resolved) if resolved != rel_path => {
+			message = Some(format!("{} is a symlink to {}", rel_path.display(), resolved.display()));
+			resolved
+		}
+		Ok(_) => rel_path,
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(EXIT_ERROR);
+		}
+	};
+	let max_lines = config.max_lines.unwrap_or(DEFAULT_MAX_LINES);
+	// a range is fast to blame however long the file is
+	if max_lines > 0 && !args.force && args.range.is_none() {
+		// errors reading the file are left for the blame to report
+		if let Ok(lines) = git::line_count(&repo, commit, &rel_path) {
+			if lines > max_lines {
+				eprintln!(
+					"{} has {} lines, more than max_lines ({}), and could take a long time to blame. \
+					 use --force to blame it anyway",
+					rel_path.display(),
+					lines,
+					max_lines
+				);
+				process::exit(EXIT_TOO_LONG);
+			}
+		}
+	}
+	let mut blame_options = git::BlameOptions {
+		ignore_whitespace: args.ignore_whitespace,
+		range: args.range,
+		..Default::default()
+	};
+	if let Some(tab_width) = args.tab_width {
+		blame_options.tab_width = tab_width;
+	}
+	let ignore_revs_path = match &args.ignore_revs_file {
+		Some(path) => PathBuf::from(path),
+		None => repo.workdir().unwrap_or(repo.path()).join(".git-blame-ignore-revs"),
+	};
+	match fs::read_to_string(&ignore_revs_path) {
+		Ok(contents) => {
+			let (revs, mut warnings) = git::parse_ignore_revs(&contents);
+			for rev in revs {
+				// git blame aborts on revs that aren't in the repo
+				if repo.find_commit(rev).is_ok() {
+					blame_options.ignore_revs.push(rev);
+				} else {
+					warnings.push(format!("commit {} not found", rev));
+				}
+			}
+			if !warnings.is_empty() {
+				message = Some(format!("{}: {}", ignore_revs_path.display(), warnings.join(", ")));
+			}
+		}
+		Err(e) if args.ignore_revs_file.is_some() => {
+			eprintln!("{}: {}", ignore_revs_path.display(), e);
+			process::exit(EXIT_ERROR);
+		}
+		Err(_) => {} // no default ignore-revs file
+	}
+
+	for rev in &args.ignore_revs {
+		match repo.revparse_single(rev).and_then(|object| object.peel_to_commit()) {
+			Ok(commit) if !blame_options.ignore_revs.contains(&commit.id()) => {
+				blame_options.ignore_revs.push(commit.id())
+			}
+			Ok(_) => {} // already in the file
+			Err(e) => {
+				eprintln!("--ignore-rev {}: {}", rev, e.message());
+				process::exit(EXIT_BAD_REV);
+			}
+		}
+	}
+
+	if args.print {
+		if let Some(message) = message {
+			eprintln!("{}", message);
+		}
+		let blame = match git::blame(&repo, &rel_path, commit, &blame_options, None) {
+			Ok(blame) => blame,
+			Err(e) => {
+				eprintln!("{}", e);
+				process::exit(EXIT_ERROR);
+			}
+		};
+		let lines: Vec<&git::BlameHunk> = match &args.author {
+			Some(author) => blame.iter().filter(|line| line.matches_author(author)).collect(),
+			None => blame.iter().collect(),
+		};
+		// absolute, so editors can open it from any directory
+		let path = match repo.workdir() {
+			Some(workdir) => workdir.join(&rel_path),
+			None => rel_path.to_owned(),
+		};
+		let abbrev = git::unambiguous_abbrev(&repo, lines.iter().map(|line| line.commit), abbrev);
+		let mut out = io::BufWriter::new(io::stdout().lock());
+		let mut result = print::print_blame(&lines, args.format, abbrev, &path, &mut out);
+		if args.summary {
+			result = result.and_then(|_| print::print_summary(&stats::summarize(&lines), &mut out));
+		}
+		match result.and_then(|_| out.flush()) {
+			Ok(()) => {}
+			Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {} // piped into head
+			Err(e) => {
+				eprintln!("{}", e);
+				process::exit(EXIT_ERROR);
+			}
+		}
+		return;
+	}
+
 	let mut app = terminal::App::new(&repo, &rel_path, commit);
-	app.blame = match git::blame(&repo, &rel_path, commit) {
-		Ok(blame) => blame,
-		Err(e) => panic!("{}", e),
+	app.keymap = keymap;
+	app.theme = theme;
+	app.blame_options = blame_options;
+	app.log_options = git::LogOptions {
+		first_parent: args.first_parent,
+		args: config.history_args,
 	};
+	if args.rev.is_none() && args.as_of.is_none() && !args.worktree {
+		app.head = repo
+			.head()
+			.and_then(|head| head.peel_to_commit())
+			.map(|commit| commit.id())
+			.ok();
+	}
+	app.abbrev = abbrev;
+	app.dim_config = config.dim;
+	if !config.layouts.is_empty() {
+		app.layouts = config.layouts;
+	}
+	app.page_scroll = args.page_scroll.or(config.page_scroll).unwrap_or_default();
+	app.regex_search = config.regex_search;
+	app.wrap_scroll = config.wrap_scroll;
+	let saved_prefs = prefs::load();
+	app.apply_prefs(&saved_prefs);
+	if args.ignore_whitespace {
+		app.blame_options.ignore_whitespace = true;
+	}
+	app.message = message;
+	if args.highlight {
+		app.highlighter = Some(Arc::new(highlight::Highlighter::new()));
+	}
+	app.blame_in_background(args.line);
 	let mut term = terminal::setup().unwrap();
-	let res = terminal::run_app(&mut term, app);
+	let res = terminal::run_app(&mut term, &mut app);
 
 	terminal::teardown(&mut term);
-	if let Err(err) = res {
+	if let Err(err) = &res {
 		println!("{:?}", err)
 	}
+	let mut prefs = app.prefs();
+	if args.ignore_whitespace && app.blame_options.ignore_whitespace {
+		prefs.ignore_whitespace = saved_prefs.ignore_whitespace; // -w is only for this run
+	}
+	if let Err(err) = prefs::save(&prefs) {
+		eprintln!("couldn't save preferences: {}", err);
+	}
+	if res.is_err() {
+		process::exit(EXIT_ERROR);
+	}
+}
+
+// opens the repo containing path and returns path relative to its workdir, or for a bare repo, path from the top of
+// its tree. errors come with the exit status for them
+fn find_repo(path: &str, repo_dir: Option<&str>) -> Result<(Repository, PathBuf), (i32, String)> {
+	let base = match repo_dir {
+		Some(dir) => PathBuf::from(dir),
+		None => env::current_dir().map_err(|e| (EXIT_ERROR, e.to_string()))?,
+	};
+	let joined = base.join(path);
+	// the file may have been deleted, in which case only its directory can be resolved
+	let abs_path = joined.canonicalize().unwrap_or_else(|_| {
+		let lexical = path_to_lexical_absolute(&joined);
+		match (
+			lexical.parent().and_then(|dir| dir.canonicalize().ok()),
+			lexical.file_name(),
+		) {
+			(Some(dir), Some(name)) => dir.join(name),
+			_ => lexical,
+		}
+	});
+	let repo = match repo_dir {
+		Some(dir) => Repository::discover(dir),
+		None if abs_path.exists() => Repository::discover(&abs_path),
+		None => Repository::open_from_env(),
+	}
+	.map_err(|e| (EXIT_NOT_A_REPO, format!("not in a git repository: {}", e.message())))?;
+	let Some(workdir) = repo.workdir() else {
+		let rel_path = git::normalize(Path::new(path)).ok_or_else(|| {
+			(
+				EXIT_NO_FILE,
+				format!("{} isn't a path from the top of the bare repository", path),
+			)
+		})?;
+		return Ok((repo, rel_path));
+	};
+	let workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.to_owned());
+	let rel_path = abs_path
+		.strip_prefix(&workdir)
+		.map_err(|_| {
+			(
+				EXIT_NO_FILE,
+				format!(
+					"{} is outside the repository at {}",
+					abs_path.display(),
+					workdir.display()
+				),
+			)
+		})?
+		.to_owned();
+	Ok((repo, rel_path))
 }
 
 fn path_to_lexical_absolute(path: &Path) -> PathBuf {
@@ -59,3 +630,139 @@ fn path_to_lexical_absolute(path: &Path) -> PathBuf {
 	}
 	absolute
 }
+
+#[cfg(test)]
+mod tests {
+	use git2::Repository;
+	use std::{fs, path::Path};
+
+	use super::{config, default_rev, find_repo, parse_args, print, Args, EXIT_NOT_A_REPO, EXIT_NO_FILE};
+	use crate::temp_dir::TempDir;
+
+	fn parse(args: &[&str]) -> Result<Args, String> {
+		parse_args(args.iter().map(|arg| arg.to_string()))
+	}
+
+	#[test]
+	fn args() {
+		assert_eq!(
+			parse(&["--line", "42", "src/main.rs", "HEAD~"]),
+			Ok(Args {
+				path: "src/main.rs".to_owned(),
+				rev: Some("HEAD~".to_owned()),
+				line: Some(42),
+				highlight: true,
+				ignore_revs_file: None,
+				..Default::default()
+			})
+		);
+		let args = parse(&["src/does_not_exist.rs:7"]).unwrap();
+		assert_eq!(args.path, "src/does_not_exist.rs");
+		assert_eq!(args.line, Some(7));
+
+		let args = parse(&["--format", "long", "src/main.rs"]).unwrap();
+		assert!(args.print);
+		assert_eq!(args.format, print::Format::Long);
+		assert!(parse(&["--format", "wide", "src/main.rs"]).is_err());
+		let args = parse(&["--author", "raylu", "--quickfix", "src/main.rs"]).unwrap();
+		assert!(args.print);
+		assert_eq!(args.author.as_deref(), Some("raylu"));
+		assert_eq!(args.format, print::Format::Quickfix);
+		assert!(parse(&["--summary", "src/main.rs"]).unwrap().print);
+		assert!(parse(&["--summary", "--json", "src/main.rs"]).is_err());
+
+		assert_eq!(parse(&["-L", "10,20", "src/main.rs"]).unwrap().range, Some((10, 20)));
+		assert_eq!(parse(&["-L3,3", "src/main.rs"]).unwrap().range, Some((3, 3)));
+		assert!(parse(&["--range", "20,10", "src/main.rs"]).is_err());
+		assert!(parse(&["--range", "0,10", "src/main.rs"]).is_err());
+		assert!(parse(&["--range", "10", "src/main.rs"]).is_err());
+
+		assert!(parse(&[]).is_err());
+		assert!(parse(&["--line", "x", "src/main.rs"]).is_err());
+		assert!(parse(&["--bogus", "src/main.rs"]).is_err());
+		assert!(parse(&["a", "b", "c"]).is_err());
+		assert_eq!(parse(&["--rev", "v1.2", "a"]).unwrap().rev.as_deref(), Some("v1.2"));
+		assert!(parse(&["--rev", "v1.2", "a", "HEAD"]).is_err());
+		assert!(parse(&["--worktree", "a"]).unwrap().worktree);
+		assert!(parse(&["--force", "a"]).unwrap().force);
+		assert!(parse(&["--worktree", "a", "HEAD"]).is_err());
+		assert_eq!(
+			parse(&["--as-of", "2024-03-31", "a"]).unwrap().as_of,
+			chrono::NaiveDate::from_ymd_opt(2024, 3, 31)
+		);
+		assert!(parse(&["--as-of", "2024-02-30", "a"]).is_err());
+		assert!(parse(&["--as-of", "2024-03-31", "--worktree", "a"]).is_err());
+		assert_eq!(parse(&["--tabwidth", "4", "a"]).unwrap().tab_width, Some(4));
+		assert_eq!(
+			parse(&["--ignore-rev", "abc", "a", "--ignore-rev", "HEAD~2"])
+				.unwrap()
+				.ignore_revs,
+			["abc", "HEAD~2"]
+		);
+		assert!(parse(&["a", "--ignore-rev"]).is_err());
+		assert!(parse(&["--tabwidth", "0", "a"]).is_err());
+		assert_eq!(parse(&["--abbrev", "12", "a"]).unwrap().abbrev, Some(12));
+		assert!(parse(&["--abbrev", "3", "a"]).is_err());
+		assert_eq!(
+			parse(&["--page-scroll", "full", "a"]).unwrap().page_scroll,
+			Some(config::PageScroll::Full)
+		);
+		assert!(parse(&["--page-scroll", "-3", "a"]).is_err());
+
+		let mut args = parse(&["a"]).unwrap();
+		default_rev(&mut args, Some("origin/main".to_owned()));
+		assert_eq!(args.rev.as_deref(), Some("origin/main"));
+		let mut args = parse(&["a", "v1.2"]).unwrap();
+		default_rev(&mut args, Some("origin/main".to_owned()));
+		assert_eq!(args.rev.as_deref(), Some("v1.2"));
+		let mut args = parse(&["--worktree", "a"]).unwrap();
+		default_rev(&mut args, Some("origin/main".to_owned()));
+		assert_eq!(args.rev, None);
+		let mut args = parse(&["a"]).unwrap();
+		default_rev(&mut args, Some(String::new()));
+		assert_eq!(args.rev, None);
+
+		// without requiring a filepath
+		assert!(parse(&["--help"]).unwrap().help);
+		assert!(parse(&["src/main.rs", "-V"]).unwrap().version);
+	}
+
+	#[test]
+	fn repo_paths() {
+		let (_, rel_path) = find_repo("src/main.rs", None).unwrap();
+		assert_eq!(rel_path, Path::new("src/main.rs"));
+		let (_, rel_path) = find_repo("main.rs", Some("src")).unwrap();
+		assert_eq!(rel_path, Path::new("src/main.rs"));
+		// deleted files can still be blamed at an older rev
+		let (_, rel_path) = find_repo("src/deleted.rs", None).unwrap();
+		assert_eq!(rel_path, Path::new("src/deleted.rs"));
+		assert_eq!(find_repo("/", None).err().unwrap().0, EXIT_NOT_A_REPO);
+	}
+
+	#[test]
+	fn worktree_and_bare_repos() {
+		let dir = TempDir::new("repos");
+		let dir = dir.path();
+		let repo = Repository::init(dir.join("main")).unwrap();
+		fs::write(dir.join("main/a.txt"), "a\n").unwrap();
+		let mut index = repo.index().unwrap();
+		index.add_path(Path::new("a.txt")).unwrap();
+		let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+		let signature = git2::Signature::now("a", "a@example.com").unwrap();
+		repo.commit(Some("HEAD"), &signature, &signature, "a", &tree, &[])
+			.unwrap();
+
+		let linked = dir.join("linked");
+		repo.worktree("linked", &linked, None).unwrap();
+		let (found, rel_path) = find_repo("a.txt", linked.to_str()).unwrap();
+		assert!(found.is_worktree());
+		assert_eq!(rel_path, Path::new("a.txt"));
+
+		let bare = dir.join("bare.git");
+		Repository::init_bare(&bare).unwrap();
+		let (found, rel_path) = find_repo("./src/../a.txt", bare.to_str()).unwrap();
+		assert!(found.is_bare());
+		assert_eq!(rel_path, Path::new("a.txt"));
+		assert_eq!(find_repo("../a.txt", bare.to_str()).err().unwrap().0, EXIT_NO_FILE);
+	}
+}
@@ -0,0 +1,72 @@
+use std::{env, error, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// the view toggles as they were when the last session quit
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Prefs {
+	pub line_numbers: Option<bool>,
+	pub ignore_whitespace: Option<bool>,
+	pub absolute_dates: Option<bool>,
+	pub heatmap: Option<bool>,
+	pub dim: Option<bool>,
+	pub folded: Option<bool>,
+	pub wrap: Option<bool>,
+	pub center: Option<bool>,
+	pub summary_width: Option<usize>,
+	pub author_badges: Option<bool>,
+	pub sha_column: Option<bool>,
+	pub author_column: Option<bool>,
+	pub date_column: Option<bool>,
+	pub minimap: Option<bool>,
+}
+
+// $XDG_STATE_HOME/git-whence, defaulting to ~/.local/state, for the prefs and the files the TUI writes
+pub fn state_dir() -> Option<PathBuf> {
+	let state_home = match env::var_os("XDG_STATE_HOME") {
+		Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+		_ => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+	};
+	Some(state_home.join("git-whence"))
+}
+
+// prefs.toml in the state_dir
+pub fn path() -> Option<PathBuf> {
+	Some(state_dir()?.join("prefs.toml"))
+}
+
+// a missing or unreadable file means the defaults, since it's only a convenience
+pub fn load() -> Prefs {
+	path()
+		.and_then(|path| fs::read_to_string(path).ok())
+		.and_then(|contents| toml::from_str(&contents).ok())
+		.unwrap_or_default()
+}
+
+pub fn save(prefs: &Prefs) -> Result<(), Box<dyn error::Error>> {
+	let path = path().ok_or("no HOME to save preferences under")?;
+	fs::create_dir_all(path.parent().unwrap())?;
+	fs::write(&path, toml::to_string(prefs)?).map_err(|e: io::Error| format!("{}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Prefs;
+
+	#[test]
+	fn round_trip() {
+		let prefs = Prefs {
+			line_numbers: Some(false),
+			summary_width: Some(40),
+			..Default::default()
+		};
+		let saved = toml::to_string(&prefs).unwrap();
+		assert_eq!(toml::from_str::<Prefs>(&saved).unwrap(), prefs);
+		// fields from newer or older versions are ignored
+		assert_eq!(
+			toml::from_str::<Prefs>("wrap = true\nsparkles = 3").unwrap().wrap,
+			Some(true)
+		);
+	}
+}
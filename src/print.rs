@@ -0,0 +1,241 @@
+use std::{io, path::Path, str::FromStr};
+
+use serde::Serialize;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+	git::{fmt_width, BlameHunk},
+	stats::Summary,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+	#[default]
+	Short, // <short-sha> <author> <code>
+	Long,     // <short-sha> <author> <date> <line number> <code>
+	Full,     // <sha> <author> <email> <date and time> <line number> <code>
+	Json,     // an array of JsonLine
+	Quickfix, // <path>:<line number>: <code>, for vim -q and other editors' error lists
+}
+
+impl FromStr for Format {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Format, String> {
+		match s {
+			"short" => Ok(Format::Short),
+			"long" => Ok(Format::Long),
+			"full" => Ok(Format::Full),
+			"json" => Ok(Format::Json),
+			"quickfix" => Ok(Format::Quickfix),
+			_ => Err(format!(
+				"unknown format {} (expected short, long, full, json, or quickfix)",
+				s
+			)),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+	commit: String,
+	author: &'a str,
+	author_email: &'a str,
+	date: String,
+	line_number: i32,
+	content: String,
+}
+
+// abbrev is how many hex digits of the sha short and long show, and path is only for quickfix,
+// which needs to say what file each line is in
+pub fn print_blame(
+	blame: &[&BlameHunk],
+	format: Format,
+	abbrev: usize,
+	path: &Path,
+	out: &mut impl io::Write,
+) -> io::Result<()> {
+	if format == Format::Json {
+		return print_json(blame, out);
+	}
+	let author_width = blame.iter().map(|line| line.author.width()).max().unwrap_or(0);
+	for line in blame {
+		let code = line.code_text();
+		let time = chrono::DateTime::<chrono::Local>::from(line.commit_time);
+		match format {
+			Format::Short => writeln!(
+				out,
+				"{:.*} {} {}",
+				abbrev,
+				line.commit,
+				fmt_width(&line.author, author_width),
+				code
+			)?,
+			Format::Long => writeln!(
+				out,
+				"{:.*} {} {} {:4} {}",
+				abbrev,
+				line.commit,
+				fmt_width(&line.author, author_width),
+				time.format("%Y-%m-%d"),
+				line.line_num,
+				code
+			)?,
+			Format::Full => writeln!(
+				out,
+				"{} {} <{}> {} {:4} {}",
+				line.commit,
+				line.author,
+				line.author_email,
+				time.format("%Y-%m-%d %H:%M:%S %z"),
+				line.line_num,
+				code
+			)?,
+			Format::Quickfix => writeln!(out, "{}:{}: {}", path.display(), line.line_num, code)?,
+			Format::Json => unreachable!(),
+		}
+	}
+	Ok(())
+}
+
+// a few lines to follow the blame, for a quick picture of who owns the file and how old it is
+pub fn print_summary(summary: &Summary, out: &mut impl io::Write) -> io::Result<()> {
+	let date = |time| chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d");
+	let plural = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+	write!(
+		out,
+		"\n{}, {}, {}",
+		plural(summary.lines, "line", "lines"),
+		plural(summary.commits, "commit", "commits"),
+		plural(summary.authors.len(), "author", "authors")
+	)?;
+	match (summary.oldest, summary.newest) {
+		(Some(oldest), Some(newest)) => writeln!(out, ", {} to {}", date(oldest), date(newest))?,
+		_ => writeln!(out)?,
+	}
+	for author in &summary.authors {
+		let percent = author.lines as f64 * 100.0 / summary.lines as f64;
+		writeln!(
+			out,
+			"{:6} {:5.1}% {} <{}>",
+			author.lines, percent, author.author, author.email
+		)?;
+	}
+	Ok(())
+}
+
+// one line at a time so the whole document is never built in memory
+fn print_json(blame: &[&BlameHunk], out: &mut impl io::Write) -> io::Result<()> {
+	writeln!(out, "[")?;
+	for (i, line) in blame.iter().enumerate() {
+		let json_line = JsonLine {
+			commit: line.commit.to_string(),
+			author: &line.author,
+			author_email: &line.author_email,
+			date: chrono::DateTime::<chrono::Local>::from(line.commit_time).to_rfc3339(),
+			line_number: line.line_num,
+			content: line.code_text(),
+		};
+		serde_json::to_writer(&mut *out, &json_line)?;
+		writeln!(out, "{}", if i + 1 < blame.len() { "," } else { "" })?;
+	}
+	writeln!(out, "]")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{path::Path, time::SystemTime};
+
+	use git2::Oid;
+
+	use super::{print_blame, print_summary, Format};
+	use crate::{git::BlameHunk, highlight::CodeSpan, stats};
+
+	fn make_blame() -> Vec<BlameHunk> {
+		let commit = Oid::from_str("116aa62bf54a39697e25f21d6cf6799f7faa1349").unwrap();
+		[("raylu", "fn main() {"), ("someone else", "}")]
+			.iter()
+			.enumerate()
+			.map(|(i, (author, code))| BlameHunk {
+				commit,
+				commit_time: SystemTime::UNIX_EPOCH,
+				author: author.to_string(),
+				author_email: String::new(),
+				summary: String::new(),
+				path: None,
+				line_num: i as i32 + 1,
+				first_in_hunk: true,
+				code: vec![CodeSpan::plain(*code)],
+				encoding: None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn short() {
+		let blame = make_blame();
+		let mut out = vec![];
+		print_blame(
+			&blame.iter().collect::<Vec<_>>(),
+			Format::Short,
+			7,
+			Path::new(""),
+			&mut out,
+		)
+		.unwrap();
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			"116aa62 raylu        fn main() {\n116aa62 someone else }\n"
+		);
+	}
+
+	#[test]
+	fn summary() {
+		let blame = make_blame();
+		let mut out = vec![];
+		print_summary(&stats::summarize(&blame.iter().collect::<Vec<_>>()), &mut out).unwrap();
+		let out = String::from_utf8(out).unwrap();
+		let mut lines = out.lines();
+		assert_eq!(lines.next(), Some(""));
+		assert!(lines.next().unwrap().starts_with("2 lines, 1 commit, 2 authors, "));
+		assert_eq!(lines.next(), Some("     1  50.0% raylu <>"));
+	}
+
+	#[test]
+	fn quickfix() {
+		let blame = make_blame();
+		let mut out = vec![];
+		let path = Path::new("/repo/src/main.rs");
+		print_blame(&[&blame[1]], Format::Quickfix, 7, path, &mut out).unwrap();
+		assert_eq!(String::from_utf8(out).unwrap(), "/repo/src/main.rs:2: }\n");
+	}
+
+	#[test]
+	fn json() {
+		let blame = make_blame();
+		let mut out = vec![];
+		print_blame(
+			&blame.iter().collect::<Vec<_>>(),
+			Format::Json,
+			7,
+			Path::new(""),
+			&mut out,
+		)
+		.unwrap();
+		let parsed: serde_json::Value = serde_json::from_slice(&out).unwrap();
+		let lines = parsed.as_array().unwrap();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0]["commit"], "116aa62bf54a39697e25f21d6cf6799f7faa1349");
+		assert_eq!(lines[1]["author"], "someone else");
+		assert_eq!(lines[1]["line_number"], 2);
+		assert_eq!(lines[1]["content"], "}");
+
+		let mut out = vec![];
+		print_blame(&[], Format::Json, 7, Path::new(""), &mut out).unwrap();
+		assert_eq!(
+			serde_json::from_slice::<serde_json::Value>(&out).unwrap(),
+			serde_json::json!([])
+		);
+	}
+}
@@ -0,0 +1,801 @@
+use std::{mem, path::Path, time};
+
+use ansi_to_tui::IntoText;
+use git2::{DiffLineType, Oid, Repository};
+use tui::{
+	style::{Color, Style},
+	text::{Line, Span, Text},
+};
+use unicode_width::UnicodeWidthStr;
+
+use git_whence::{
+	git::{char_width, fmt_age, fmt_width, git_time, BlameHunk, FileCommit, DEFAULT_ABBREV},
+	highlight::{CodeSpan, Highlighter},
+};
+
+#[derive(Clone, Copy)]
+pub struct LineFormat {
+	pub abbrev: usize,       // hex digits of the sha
+	pub code_scroll: usize,  // skips that many columns of the code
+	pub gutter_width: usize, // 0 hides line numbers
+	pub absolute_dates: bool,
+	pub summary_width: usize, // 0 hides the commit summary column
+	pub gutter_color: Color,  // also used for the fold summary
+	pub author_badges: bool,  // initials on a color picked by author email, on every line
+	pub sha: bool,            // the commit columns, on the first line of each hunk
+	pub author: bool,
+	pub date: bool,
+}
+
+impl Default for LineFormat {
+	fn default() -> LineFormat {
+		LineFormat {
+			abbrev: DEFAULT_ABBREV,
+			code_scroll: 0,
+			gutter_width: 0,
+			absolute_dates: false,
+			summary_width: 0,
+			gutter_color: Color::default(),
+			author_badges: false,
+			sha: true,
+			author: true,
+			date: true,
+		}
+	}
+}
+
+const AUTHOR_WIDTH: usize = 12;
+const DATE_WIDTH: usize = 13;
+
+// the code in the colors it was highlighted with
+pub fn code_spans(code: &[CodeSpan]) -> Vec<Span<'static>> {
+	code.iter()
+		.map(|span| match span.color {
+			Some((r, g, b)) => Span::styled(span.text.clone(), Style::default().fg(Color::Rgb(r, g, b))),
+			None => Span::raw(span.text.clone()),
+		})
+		.collect()
+}
+
+// the TUI's lines for a line of the blame
+pub trait BlameLines {
+	fn to_line(&self, format: &LineFormat) -> Line<'static>;
+	// like to_line, but breaks the code into as many lines as it takes to fit in width
+	fn to_wrapped_text(&self, format: &LineFormat, width: usize) -> Text<'static>;
+	// a summary of this line and the next len - 1, which are all from the same commit
+	fn to_fold_line(&self, format: &LineFormat, len: usize) -> Line<'static>;
+}
+
+impl BlameLines for BlameHunk {
+	fn to_line(&self, format: &LineFormat) -> Line<'static> {
+		let mut spans = header_spans(self, format, self.first_in_hunk);
+		spans.push(Span::raw(" "));
+		spans.extend(skip_columns(&code_spans(&self.code), format.code_scroll));
+		Line::from(spans)
+	}
+
+	fn to_wrapped_text(&self, format: &LineFormat, width: usize) -> Text<'static> {
+		let mut first = header_spans(self, format, self.first_in_hunk);
+		first.push(Span::raw(" "));
+		let indent: usize = first.iter().map(|span| span.content.width()).sum();
+		let code = skip_columns(&code_spans(&self.code), format.code_scroll);
+		let mut chunks = wrap_spans(&code, width.saturating_sub(indent).max(1)).into_iter();
+		first.extend(chunks.next().unwrap_or_default());
+		let mut lines = vec![Line::from(first)];
+		for chunk in chunks {
+			let mut spans = vec![Span::raw(" ".repeat(indent))];
+			spans.extend(chunk);
+			lines.push(Line::from(spans));
+		}
+		Text::from(lines)
+	}
+
+	fn to_fold_line(&self, format: &LineFormat, len: usize) -> Line<'static> {
+		let mut spans = header_spans(self, format, true);
+		spans.push(Span::styled(
+			format!(" ▸ {} lines", len),
+			Style::default().fg(format.gutter_color),
+		));
+		Line::from(spans)
+	}
+}
+
+// the line number and, if show_commit, the sha, author, and age
+fn header_spans(line: &BlameHunk, format: &LineFormat, show_commit: bool) -> Vec<Span<'static>> {
+	let mut spans = vec![];
+	if format.gutter_width > 0 {
+		spans.push(Span::styled(
+			format!("{:>width$} ", line.line_num, width = format.gutter_width),
+			Style::default().fg(format.gutter_color),
+		));
+	}
+	if format.author_badges && line.author.is_empty() {
+		spans.push(Span::raw("   ")); // not blamed yet
+	} else if format.author_badges {
+		spans.push(Span::styled(
+			format!("{:2}", initials(&line.author)),
+			Style::default().fg(Color::White).bg(author_color(&line.author_email)),
+		));
+		spans.push(Span::raw(" "));
+	}
+	if show_commit {
+		spans.extend(join_columns(commit_columns(
+			line.commit,
+			&line.author,
+			line.commit_time,
+			format,
+		)));
+	} else {
+		let widths = [
+			(format.sha, format.abbrev),
+			(format.author, AUTHOR_WIDTH),
+			(format.date, DATE_WIDTH),
+		];
+		let shown: Vec<usize> = widths
+			.iter()
+			.filter(|(shown, _)| *shown)
+			.map(|(_, width)| *width)
+			.collect();
+		// with the spaces between them
+		let width = shown.iter().sum::<usize>() + shown.len().saturating_sub(1);
+		spans.push(Span::raw(" ".repeat(width)));
+	}
+	if format.summary_width > 0 {
+		let summary = if show_commit { line.summary.as_str() } else { "" };
+		spans.push(Span::styled(
+			format!(" {}", fmt_width(summary, format.summary_width)),
+			Style::default().fg(Color::Gray),
+		));
+	}
+	spans
+}
+
+// the first letters of the first and last names, or the first two of a single name
+fn initials(name: &str) -> String {
+	let mut words = name
+		.split_whitespace()
+		.filter_map(|word| word.chars().find(|c| c.is_alphanumeric()));
+	let initials: String = match (words.next(), words.next_back()) {
+		(Some(first), Some(last)) => [first, last].iter().collect(),
+		(Some(_), None) => name.chars().filter(|c| c.is_alphanumeric()).take(2).collect(),
+		_ => "?".to_owned(),
+	};
+	initials.to_uppercase().chars().take(2).collect()
+}
+
+// background colors that white text is readable on
+const AUTHOR_COLORS: [u8; 12] = [24, 25, 28, 29, 30, 53, 54, 88, 89, 94, 95, 130];
+
+// the same for an email on every run, so people can learn their colors
+pub fn author_color(email: &str) -> Color {
+	// FNV-1a, since std's hasher can change between releases
+	let hash = email.to_lowercase().bytes().fold(0xcbf29ce484222325_u64, |hash, byte| {
+		(hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+	});
+	Color::Indexed(AUTHOR_COLORS[(hash % AUTHOR_COLORS.len() as u64) as usize])
+}
+
+// splits spans into lines of at most width columns, or one character when that's wider
+fn wrap_spans(spans: &[Span<'static>], width: usize) -> Vec<Vec<Span<'static>>> {
+	let mut lines = vec![vec![]];
+	let mut used = 0;
+	for span in spans {
+		let mut rest: &str = &span.content;
+		while !rest.is_empty() {
+			let mut split = 0;
+			for (i, c) in rest.char_indices() {
+				let c_width = char_width(c);
+				if used + c_width > width && used > 0 {
+					break;
+				}
+				used += c_width;
+				split = i + c.len_utf8();
+			}
+			if split > 0 {
+				lines
+					.last_mut()
+					.unwrap()
+					.push(Span::styled(rest[..split].to_owned(), span.style));
+				rest = &rest[split..];
+			}
+			if !rest.is_empty() {
+				lines.push(vec![]);
+				used = 0;
+			}
+		}
+	}
+	lines
+}
+
+// drops the first n columns, leaving a space for the half of a wide character that's cut
+fn skip_columns(spans: &[Span<'static>], mut n: usize) -> Vec<Span<'static>> {
+	let mut out = vec![];
+	for span in spans {
+		if n == 0 {
+			out.push(span.clone());
+			continue;
+		}
+		let mut content = String::new();
+		for c in span.content.chars() {
+			if n == 0 {
+				content.push(c);
+				continue;
+			}
+			let c_width = char_width(c);
+			if c_width > n {
+				content.push_str(&" ".repeat(c_width - n));
+			}
+			n = n.saturating_sub(c_width);
+		}
+		if !content.is_empty() {
+			out.push(Span::styled(content, span.style));
+		}
+	}
+	out
+}
+
+pub fn show(repo: &Repository, commit_id: Oid, colors: &DiffColors) -> Text<'static> {
+	if commit_id.is_zero() {
+		return uncommitted_diff(repo, None, colors);
+	}
+	let commit = match repo.find_commit(commit_id) {
+		Ok(commit) => commit,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let diff = match diff_for_commit(repo, &commit, None) {
+		Ok(diff) => diff,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let (author, _) = mailmapped(repo, &commit);
+	let mut lines = vec![
+		Line::from(Span::styled(
+			commit.id().to_string(),
+			Style::default().fg(Color::Yellow),
+		)),
+		Line::from(format!(
+			"author: {} <{}>",
+			author.name().unwrap_or_default(),
+			author.email().unwrap_or_default()
+		)),
+		Line::from(format!("date: {}", fmt_time(commit.time()))),
+		Line::default(),
+		Line::from(commit.summary().unwrap_or_default().to_owned()),
+		Line::default(),
+	];
+	if let Some(body) = commit.body() {
+		push_lines(&mut lines, body, Color::Reset);
+		lines.push(Line::default());
+	}
+	if let Err(e) = push_diff(&mut lines, &diff, colors) {
+		return Text::raw(e.to_string());
+	}
+	Text::from(lines)
+}
+
+// the commit's changes to a single file, headed by its sha in abbrev hex digits
+pub fn show_file(repo: &Repository, commit_id: Oid, path: &Path, abbrev: usize, colors: &DiffColors) -> Text<'static> {
+	if commit_id.is_zero() {
+		return uncommitted_diff(repo, Some(path), colors);
+	}
+	let commit = match repo.find_commit(commit_id) {
+		Ok(commit) => commit,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let diff = match diff_for_commit(repo, &commit, Some(path)) {
+		Ok(diff) => diff,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let mut lines = vec![
+		Line::from(vec![
+			Span::styled(
+				format!("{:.*}", abbrev, commit.id()),
+				Style::default().fg(Color::Yellow),
+			),
+			Span::raw(" "),
+			Span::raw(commit.summary().unwrap_or_default().to_owned()),
+		]),
+		Line::default(),
+	];
+	if let Err(e) = push_diff(&mut lines, &diff, colors) {
+		return Text::raw(e.to_string());
+	}
+	Text::from(lines)
+}
+
+// changes in the index and working tree since HEAD, of just path if given
+fn uncommitted_diff(repo: &Repository, path: Option<&Path>, colors: &DiffColors) -> Text<'static> {
+	let mut options = git2::DiffOptions::new();
+	if let Some(path) = path {
+		options.pathspec(path).disable_pathspec_match(true);
+	}
+	let head_tree = repo.head().and_then(|head| head.peel_to_tree()).ok();
+	let diff = match repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut options)) {
+		Ok(diff) => diff,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let mut lines = vec![
+		Line::from(Span::styled("not committed yet", Style::default().fg(Color::Yellow))),
+		Line::default(),
+	];
+	if let Err(e) = push_diff(&mut lines, &diff, colors) {
+		return Text::raw(e.to_string());
+	}
+	Text::from(lines)
+}
+
+// the lines of a diff in show and show_file, from the theme in the TUI
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffColors {
+	pub file_header: Color,
+	pub hunk_header: Color,
+	pub added: Color,
+	pub removed: Color,
+}
+
+impl Default for DiffColors {
+	fn default() -> DiffColors {
+		DiffColors {
+			file_header: Color::Cyan,
+			hunk_header: Color::Blue,
+			added: Color::Green,
+			removed: Color::Red,
+		}
+	}
+}
+
+fn push_diff(lines: &mut Vec<Line>, diff: &git2::Diff, colors: &DiffColors) -> Result<(), git2::Error> {
+	let diff_cb = |_: git2::DiffDelta, _: Option<git2::DiffHunk>, diff_line: git2::DiffLine| -> bool {
+		let content = String::from_utf8_lossy(diff_line.content());
+		let sigil = match diff_line.origin_value() {
+			DiffLineType::Addition => "+",
+			DiffLineType::Deletion => "-",
+			DiffLineType::Context => " ",
+			_ => "",
+		};
+		let line = format!("{}{}", sigil, content.replace('\t', "    ").trim_end_matches('\n'));
+		let color = match diff_line.origin_value() {
+			DiffLineType::FileHeader => colors.file_header,
+			DiffLineType::HunkHeader => colors.hunk_header,
+			DiffLineType::Addition | DiffLineType::AddEOFNL => colors.added,
+			DiffLineType::Deletion | DiffLineType::DeleteEOFNL => colors.removed,
+			_ => Color::Reset,
+		};
+		push_lines(lines, &line, color);
+		true
+	};
+	diff.print(git2::DiffFormat::Patch, diff_cb)
+}
+
+pub fn commit_details(repo: &Repository, commit_id: Oid) -> Text<'static> {
+	if commit_id.is_zero() {
+		return Text::raw("not committed yet");
+	}
+	let commit = match repo.find_commit(commit_id) {
+		Ok(commit) => commit,
+		Err(e) => return Text::raw(e.to_string()),
+	};
+	let (author, committer) = mailmapped(repo, &commit);
+	let mut lines = vec![
+		Line::from(Span::styled(
+			commit.id().to_string(),
+			Style::default().fg(Color::Yellow),
+		)),
+		Line::from(format!(
+			"author: {} <{}>",
+			author.name().unwrap_or_default(),
+			author.email().unwrap_or_default()
+		)),
+		Line::from(format!(
+			"author date: {} ({})",
+			fmt_time(author.when()),
+			fmt_age(git_time(author.when()))
+		)),
+		Line::from(format!(
+			"committer: {} <{}>",
+			committer.name().unwrap_or_default(),
+			committer.email().unwrap_or_default()
+		)),
+		Line::from(format!(
+			"commit date: {} ({})",
+			fmt_time(committer.when()),
+			fmt_age(git_time(committer.when()))
+		)),
+		shortstat(repo, &commit),
+		Line::default(),
+	];
+	push_lines(
+		&mut lines,
+		commit.message().unwrap_or_default().trim_end(),
+		Color::Reset,
+	);
+	Text::from(lines)
+}
+
+// counting lines diffs every file, which takes too long for the biggest merges, so they only get the file count
+const SHORTSTAT_MAX_FILES: usize = 1000;
+
+// like git show --shortstat, against the first parent
+fn shortstat(repo: &Repository, commit: &git2::Commit) -> Line<'static> {
+	let plural = |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+	let diff = match diff_for_commit(repo, commit, None) {
+		Ok(diff) => diff,
+		Err(e) => return Line::from(e.message().to_owned()),
+	};
+	let files = plural(diff.deltas().len(), "file changed", "files changed");
+	if diff.deltas().len() > SHORTSTAT_MAX_FILES {
+		return Line::from(files);
+	}
+	match diff.stats() {
+		Ok(stats) => Line::from(vec![
+			Span::raw(format!("{}, ", files)),
+			Span::styled(
+				plural(stats.insertions(), "insertion(+)", "insertions(+)"),
+				Style::default().fg(Color::Green),
+			),
+			Span::raw(", "),
+			Span::styled(
+				plural(stats.deletions(), "deletion(-)", "deletions(-)"),
+				Style::default().fg(Color::Red),
+			),
+		]),
+		Err(e) => Line::from(format!("{}, {}", files, e.message())),
+	}
+}
+
+// the author and committer with .mailmap applied, as git blame does, or as recorded if it can't be read
+fn mailmapped(repo: &Repository, commit: &git2::Commit) -> (git2::Signature<'static>, git2::Signature<'static>) {
+	let mailmap = repo.mailmap().ok();
+	let author = mailmap
+		.as_ref()
+		.and_then(|mailmap| commit.author_with_mailmap(mailmap).ok());
+	let committer = mailmap
+		.as_ref()
+		.and_then(|mailmap| commit.committer_with_mailmap(mailmap).ok());
+	(
+		author.unwrap_or_else(|| commit.author().to_owned()),
+		committer.unwrap_or_else(|| commit.committer().to_owned()),
+	)
+}
+
+// the day, or how long ago like fmt_age
+fn fmt_date(time: time::SystemTime, absolute: bool) -> String {
+	match absolute {
+		true => chrono::DateTime::<chrono::Local>::from(time)
+			.format("%Y-%m-%d")
+			.to_string(),
+		false => fmt_age(time),
+	}
+}
+
+fn fmt_time(time: git2::Time) -> String {
+	let time = chrono::DateTime::from_timestamp(time.seconds(), 0).unwrap();
+	time.with_timezone(&chrono::Local).to_string()
+}
+
+// against the first parent, or everything added for a root commit
+fn diff_for_commit<'a>(
+	repo: &'a Repository,
+	commit: &git2::Commit<'a>,
+	path: Option<&Path>,
+) -> Result<git2::Diff<'a>, git2::Error> {
+	let parent_tree = match commit.parent(0) {
+		Ok(parent) => Some(parent.tree()?),
+		Err(_) => None,
+	};
+	let mut options = git2::DiffOptions::new();
+	if let Some(path) = path {
+		options.pathspec(path).disable_pathspec_match(true);
+	}
+	repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut options))
+}
+
+fn push_lines(lines: &mut Vec<Line>, s: &str, color: Color) {
+	for line in s.split('\n') {
+		lines.push(Line::from(Span::styled(line.to_owned(), Style::default().fg(color))));
+	}
+}
+
+// syntax highlights the code in git log -L output, a hunk at a time, leaving each line's +, -, or space in the diff's
+// color. in_hunk carries over from the previous chunk of the output
+pub fn highlight_history(
+	text: Text<'static>,
+	path: &Path,
+	highlighter: &Highlighter,
+	in_hunk: &mut bool,
+) -> Text<'static> {
+	let mut lines = Vec::with_capacity(text.lines.len());
+	let mut hunk = vec![];
+	for line in text.lines {
+		let plain = plain_text(&line);
+		if *in_hunk && matches!(plain.chars().next(), Some(' ' | '+' | '-')) {
+			hunk.push(line);
+			continue;
+		}
+		lines.extend(highlight_hunk(mem::take(&mut hunk), path, highlighter));
+		// the commit header, the diff header, or the blank line after a hunk
+		*in_hunk = plain.starts_with("@@");
+		lines.push(line);
+	}
+	lines.extend(highlight_hunk(hunk, path, highlighter));
+	Text::from(lines)
+}
+
+fn highlight_hunk(hunk: Vec<Line<'static>>, path: &Path, highlighter: &Highlighter) -> Vec<Line<'static>> {
+	if hunk.is_empty() {
+		return hunk;
+	}
+	let plain: Vec<String> = hunk.iter().map(plain_text).collect();
+	// the old and new versions of the code, so an unclosed string or comment on one side doesn't run into the other
+	let side = |skipped: char| {
+		let code: Vec<&str> = plain
+			.iter()
+			.filter(|line| !line.starts_with(skipped))
+			.map(|line| &line[1..])
+			.collect();
+		highlighter.highlight(path, &code)
+	};
+	let (Some(old), Some(new)) = (side('+'), side('-')) else {
+		return hunk;
+	};
+	let (mut old, mut new) = (old.into_iter(), new.into_iter());
+	hunk.into_iter()
+		.zip(&plain)
+		.map(|(line, plain)| {
+			let code = match plain.chars().next() {
+				Some('-') => old.next(),
+				Some('+') => new.next(),
+				_ => old.next().and(new.next()),
+			}
+			.unwrap_or_default();
+			let sigil_style = line.spans.first().map_or(Style::default(), |span| span.style);
+			let mut spans = vec![Span::styled(plain[..1].to_owned(), sigil_style)];
+			spans.extend(code_spans(&code));
+			Line::from(spans)
+		})
+		.collect()
+}
+
+fn plain_text(line: &Line) -> String {
+	line.spans.iter().map(|span| span.content.as_ref()).collect()
+}
+
+// git's colored output
+pub fn ansi_text(buf: Vec<u8>) -> Text<'static> {
+	match buf.into_text() {
+		Ok(t) => t,
+		Err(e) => Text::raw(format!("ansi_to_tui:\n{}", e)),
+	}
+}
+
+// a commit from git::file_log with the blame's columns, whichever the layout shows, and the file's old name from before
+// a rename of path
+pub fn file_commit_line(commit: &FileCommit, format: &LineFormat, path: &Path) -> Line<'static> {
+	let mut columns = commit_columns(commit.commit, &commit.author, commit.time, format);
+	if commit.path != path {
+		columns.push(Span::styled(
+			commit.path.display().to_string(),
+			Style::default().fg(Color::LightBlue),
+		));
+	}
+	columns.push(Span::raw(commit.summary.clone()));
+	Line::from(join_columns(columns))
+}
+
+// the sha, author, and date, whichever the format shows
+fn commit_columns(commit: Oid, author: &str, time: time::SystemTime, format: &LineFormat) -> Vec<Span<'static>> {
+	let mut columns = vec![];
+	if format.sha {
+		columns.push(Span::styled(
+			format!("{:.*}", format.abbrev, commit),
+			Style::default().fg(Color::Yellow),
+		));
+	}
+	if format.author {
+		columns.push(Span::raw(fmt_width(author, AUTHOR_WIDTH)));
+	}
+	if format.date {
+		columns.push(Span::styled(
+			fmt_width(&fmt_date(time, format.absolute_dates), DATE_WIDTH),
+			Style::default().fg(Color::LightRed),
+		));
+	}
+	columns
+}
+
+fn join_columns(columns: Vec<Span<'static>>) -> Vec<Span<'static>> {
+	let mut spans = vec![];
+	for (i, column) in columns.into_iter().enumerate() {
+		if i > 0 {
+			spans.push(Span::raw(" "));
+		}
+		spans.push(column);
+	}
+	spans
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::{Path, PathBuf};
+
+	use git2::Oid;
+	use tui::{
+		style::{Color, Style},
+		text::{Line, Span, Text},
+	};
+
+	use git_whence::{
+		git::{BlameHunk, FileCommit},
+		highlight::{CodeSpan, Highlighter},
+	};
+
+	use super::{
+		author_color, commit_details, file_commit_line, highlight_history, initials, skip_columns, wrap_spans,
+		BlameLines, LineFormat,
+	};
+	use crate::temp_dir::TempDir;
+
+	#[test]
+	fn history_highlighting() {
+		let output = [
+			"commit 1234",
+			"",
+			"    - not code",
+			"",
+			"diff --git a/src/main.rs b/src/main.rs",
+			"--- a/src/main.rs",
+			"+++ b/src/main.rs",
+			"@@ -1,2 +1,2 @@",
+			"-fn main() {",
+			"+fn main() -> Result<(), String> {",
+			" }",
+			"",
+		];
+		let mut text = Text::from(output.map(Line::from).to_vec());
+		text.lines[8].spans[0].style = Style::default().fg(Color::Red);
+		let highlighter = Highlighter::new();
+		let mut in_hunk = false;
+		let highlighted = highlight_history(text, Path::new("src/main.rs"), &highlighter, &mut in_hunk);
+		let plain: Vec<String> = highlighted.lines.iter().map(super::plain_text).collect();
+		assert_eq!(plain, output);
+		for (i, line) in highlighted.lines.iter().enumerate() {
+			assert_eq!(line.spans.len() > 1, (8..=10).contains(&i), "line {}", i);
+		}
+		assert_eq!(highlighted.lines[8].spans[0].content, "-");
+		assert_eq!(highlighted.lines[8].spans[0].style.fg, Some(Color::Red));
+		assert!(!in_hunk);
+
+		// a hunk split between chunks
+		let mut in_hunk = true;
+		let text = Text::from(vec![Line::from("+let x = 1;")]);
+		let highlighted = highlight_history(text, Path::new("src/main.rs"), &highlighter, &mut in_hunk);
+		assert!(highlighted.lines[0].spans.len() > 1);
+		assert!(in_hunk);
+
+		// the comment opened on the old side doesn't swallow the new side
+		let text = Text::from(vec![Line::from("-/* gone"), Line::from("+let x = 1;")]);
+		let highlighted = highlight_history(text, Path::new("src/main.rs"), &highlighter, &mut in_hunk);
+		let plain: Vec<String> = highlighted.lines.iter().map(super::plain_text).collect();
+		assert_eq!(plain, ["-/* gone", "+let x = 1;"]);
+		assert!(highlighted.lines[1].spans.len() > 2);
+	}
+
+	#[test]
+	fn mailmap() {
+		let dir = TempDir::new("mailmap");
+		let repo = git2::Repository::init(dir.path()).unwrap();
+		let signature = git2::Signature::now("old name", "old@example.com").unwrap();
+		let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+		let commit = repo.commit(None, &signature, &signature, "empty", &tree, &[]).unwrap();
+		std::fs::write(
+			dir.path().join(".mailmap"),
+			"New Name <new@example.com> <old@example.com>\n",
+		)
+		.unwrap();
+
+		let text = commit_details(&repo, commit);
+		let line = |i: usize| -> String { text.lines[i].spans.iter().map(|span| span.content.as_ref()).collect() };
+		assert_eq!(line(1), "author: New Name <new@example.com>");
+		assert_eq!(line(5), "0 files changed, 0 insertions(+), 0 deletions(-)");
+	}
+
+	#[test]
+	fn badges() {
+		assert_eq!(initials("Ada Lovelace"), "AL");
+		assert_eq!(initials("Grace Brewster Murray Hopper"), "GH");
+		assert_eq!(initials("raylu"), "RA");
+		assert_eq!(initials("é"), "É");
+		assert_eq!(initials(""), "?");
+		assert_eq!(author_color("raylu@example.com"), author_color("RAYLU@example.com"));
+	}
+
+	#[test]
+	fn wrap() {
+		let style = Style::default().fg(tui::style::Color::Red);
+		let spans = vec![Span::raw("fn main"), Span::styled("() {}", style)];
+		let lines = wrap_spans(&spans, 4);
+		let text: Vec<Vec<&str>> = lines
+			.iter()
+			.map(|line| line.iter().map(|span| span.content.as_ref()).collect())
+			.collect();
+		assert_eq!(text, [vec!["fn m"], vec!["ain", "("], vec![") {}"]]);
+		assert_eq!(lines[1][1].style, style);
+		assert_eq!(wrap_spans(&[], 4).len(), 1);
+		// wide characters take two columns and aren't split across lines
+		let lines = wrap_spans(&[Span::raw("a漢字b")], 4);
+		let text: Vec<&str> = lines.iter().map(|line| line[0].content.as_ref()).collect();
+		assert_eq!(text, ["a漢", "字b"]);
+		let text = |spans: Vec<Span<'static>>| -> String { spans.iter().map(|span| span.content.as_ref()).collect() };
+		assert_eq!(text(skip_columns(&[Span::raw("漢字x")], 1)), " 字x");
+		assert_eq!(text(skip_columns(&[Span::raw("ab"), Span::raw("漢")], 2)), "漢");
+	}
+
+	#[test]
+	fn columns() {
+		let mut line = BlameHunk {
+			commit: Oid::from_str("116aa62bf54a39697e25f21d6cf6799f7faa1349").unwrap(),
+			commit_time: std::time::SystemTime::UNIX_EPOCH,
+			author: "raylu".to_owned(),
+			author_email: String::new(),
+			summary: String::new(),
+			path: None,
+			line_num: 1,
+			first_in_hunk: true,
+			code: vec![CodeSpan::plain("x")],
+			encoding: None,
+		};
+		let text = |line: &BlameHunk, format: &LineFormat| -> String {
+			line.to_line(format)
+				.spans
+				.iter()
+				.map(|span| span.content.as_ref())
+				.collect()
+		};
+		for format in [
+			LineFormat::default(),
+			LineFormat {
+				author: false,
+				date: false,
+				..Default::default()
+			},
+			LineFormat {
+				sha: false,
+				..Default::default()
+			},
+		] {
+			let first = text(&line, &format);
+			line.first_in_hunk = false;
+			// the rest of the hunk is blank where the commit columns would be
+			assert_eq!(text(&line, &format).len(), first.len());
+			line.first_in_hunk = true;
+		}
+		let minimal = LineFormat {
+			author: false,
+			date: false,
+			..Default::default()
+		};
+		assert_eq!(text(&line, &minimal), "116aa62 x");
+
+		// the file history's rows have the same columns
+		let file_commit = FileCommit {
+			commit: line.commit,
+			path: PathBuf::from("src/old.rs"),
+			author: "raylu".to_owned(),
+			time: line.commit_time,
+			summary: "rename it".to_owned(),
+		};
+		let text = |format: &LineFormat| -> String {
+			file_commit_line(&file_commit, format, Path::new("src/new.rs"))
+				.spans
+				.iter()
+				.map(|span| span.content.as_ref())
+				.collect()
+		};
+		assert_eq!(text(&minimal), "116aa62 src/old.rs rename it");
+		let no_sha = LineFormat {
+			sha: false,
+			date: false,
+			..Default::default()
+		};
+		assert_eq!(text(&no_sha), "raylu        src/old.rs rename it");
+	}
+}
@@ -0,0 +1,112 @@
+use std::{
+	collections::{HashMap, HashSet},
+	time::SystemTime,
+};
+
+use crate::git::BlameHunk;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AuthorLines<'a> {
+	pub author: &'a str,
+	pub email: &'a str,
+	pub lines: usize,
+}
+
+// what --summary prints after the blame
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary<'a> {
+	pub lines: usize,
+	pub commits: usize, // not counting uncommitted lines
+	pub authors: Vec<AuthorLines<'a>>,
+	pub oldest: Option<SystemTime>,
+	pub newest: Option<SystemTime>,
+}
+
+// sorted by number of lines, most first
+pub fn by_author<'a>(blame: impl IntoIterator<Item = &'a BlameHunk>) -> Vec<AuthorLines<'a>> {
+	let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+	for line in blame {
+		*counts.entry((&line.author, &line.author_email)).or_default() += 1;
+	}
+	let mut authors: Vec<AuthorLines> = counts
+		.into_iter()
+		.map(|((author, email), lines)| AuthorLines { author, email, lines })
+		.collect();
+	authors.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.author.cmp(b.author)));
+	authors
+}
+
+pub fn summarize<'a>(blame: &[&'a BlameHunk]) -> Summary<'a> {
+	let committed = || blame.iter().filter(|line| !line.commit.is_zero());
+	Summary {
+		lines: blame.len(),
+		commits: committed().map(|line| line.commit).collect::<HashSet<_>>().len(),
+		authors: by_author(blame.iter().copied()),
+		oldest: committed().map(|line| line.commit_time).min(),
+		newest: committed().map(|line| line.commit_time).max(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, SystemTime};
+
+	use git2::Oid;
+
+	use super::{by_author, summarize, AuthorLines};
+	use crate::git::BlameHunk;
+
+	fn hunk(author: &str) -> BlameHunk {
+		BlameHunk {
+			commit: Oid::zero(),
+			commit_time: SystemTime::UNIX_EPOCH,
+			author: author.to_owned(),
+			author_email: format!("{}@example.com", author),
+			summary: String::new(),
+			path: None,
+			line_num: 1,
+			first_in_hunk: true,
+			code: vec![],
+			encoding: None,
+		}
+	}
+
+	#[test]
+	fn authors() {
+		let blame = vec![hunk("b"), hunk("a"), hunk("b"), hunk("c"), hunk("a"), hunk("b")];
+		assert_eq!(
+			by_author(&blame),
+			vec![
+				AuthorLines {
+					author: "b",
+					email: "b@example.com",
+					lines: 3
+				},
+				AuthorLines {
+					author: "a",
+					email: "a@example.com",
+					lines: 2
+				},
+				AuthorLines {
+					author: "c",
+					email: "c@example.com",
+					lines: 1
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn summary() {
+		let mut blame = [hunk("a"), hunk("b"), hunk("a")];
+		blame[1].commit = Oid::from_str("1234").unwrap();
+		blame[1].commit_time = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+		blame[2].commit = Oid::from_str("5678").unwrap();
+		let summary = summarize(&blame.iter().collect::<Vec<_>>());
+		assert_eq!(summary.lines, 3);
+		assert_eq!(summary.commits, 2); // the first line isn't committed
+		assert_eq!(summary.authors.len(), 2);
+		assert_eq!(summary.oldest, Some(SystemTime::UNIX_EPOCH));
+		assert_eq!(summary.newest, Some(blame[1].commit_time));
+	}
+}
@@ -0,0 +1,28 @@
+use std::{
+	env, fs,
+	path::{Path, PathBuf},
+	process,
+};
+
+// a directory for a test's repositories, removed when dropped so a failed assert doesn't leave it behind
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+	// name keeps it apart from the other tests' directories, which they may be using at the same time
+	pub fn new(name: &str) -> TempDir {
+		let dir = env::temp_dir().join(format!("git-whence-{}-{}", name, process::id()));
+		_ = fs::remove_dir_all(&dir); // left over from a run that was killed
+		fs::create_dir_all(&dir).unwrap();
+		TempDir(dir)
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		_ = fs::remove_dir_all(&self.0);
+	}
+}
@@ -1,49 +1,484 @@
 use crossterm::{
+	cursor,
 	event::{
-		self, Event,
+		self, DisableMouseCapture, EnableMouseCapture, Event,
 		KeyCode::{self, Char},
-		KeyEvent, KeyModifiers,
+		KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 	},
 	execute,
 	terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use git2::{Oid, Repository};
+use lru::LruCache;
+use regex::{Regex, RegexBuilder};
 use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	env,
 	error::Error,
-	io::{self, Stdout},
+	fs,
+	io::{self, Stdout, Write},
+	mem,
+	num::NonZeroUsize,
+	panic,
 	path::{Path, PathBuf},
+	process,
+	sync::Arc,
+	thread,
+	time::{Duration, Instant, SystemTime},
 };
 use tui::{
 	backend::CrosstermBackend,
-	layout::{Constraint, Direction, Layout, Rect},
+	layout::{Alignment, Constraint, Direction, Layout, Rect},
 	style::{Color, Modifier, Style},
 	text::{Line, Span, Text},
 	widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 	Frame, Terminal,
 };
 
-use crate::git;
+use unicode_width::UnicodeWidthStr;
+
+use git_whence::{git, highlight::Highlighter, print, stats};
+
+use crate::{
+	config::{self, Column, ColumnLayout, DimConfig, PageScroll},
+	keys::{Action, Keymap},
+	prefs::{self, Prefs},
+	render::{self, BlameLines},
+	theme::Theme,
+};
 
 pub struct App<'a> {
-	pub blame: Vec<git::BlameHunk<'a>>,
-	blame_state: ListState,
+	pub blame: Vec<git::BlameHunk>,
+	blame_state: ListState,   // the selection is always an index into blame, even when folded
+	blame_area: Rect,         // where the list was last rendered, for mapping mouse clicks
+	minimap_area: Rect,       // likewise for the minimap, which is empty when it's hidden
+	item_heights: Vec<usize>, // of each row last rendered, which can be more than 1 when wrapping
 	repo: &'a Repository,
-	commit_stack: Vec<CommitPath>,      // pushed by `b`, popped by `B`
-	right_panel: Option<Text<'static>>, // activated by `w` or <enter>
+	commit_stack: Vec<CommitPath>,   // pushed by `b`, popped by `B`
+	right_panel: Option<RightPanel>, // activated by `w`, `f`, `i`, `s`, or <enter>
 	line_history_scroll: u16,
+	line_format: render::LineFormat, // code_scroll is moved by ← and →, absolute_dates by `t`, summary_width by `c` and `C`
+	line_numbers: bool,              // toggled by `#`
+	commit_details: Option<(Oid, Text<'static>)>, // cached until a different commit is selected
 	popup: Option<Text<'static>>,
+	parent_prompt: Option<ParentPrompt>, // shown as a popup when `b` is pressed on a merge
+	file_picker: Option<FilePicker>,     // opened by `e`
+	stack_picker: Option<StackPicker>,   // opened by `S`
+	log_picker: Option<LogPicker>,       // opened by `H`
 	search: Option<Search>,
+	author_filter: Option<Search>, // set by `A`; lines by other authors are dimmed and skipped by j and k
 	line_number: Option<String>,
+	marks: Marks,
+	pending_mark: Option<Action>,           // `m` or `'` waiting for the mark's letter
+	pending_edit: Option<(PathBuf, usize)>, // set by `O`; run_app suspends the UI to run the editor
+	rev_prompt: Option<RevPrompt>,
+	compare: Option<Compare>,    // opened by `D`, in place of the right panel
+	pub message: Option<String>, // shown in the status bar until the next key press or MESSAGE_TIMEOUT
+	errors: ErrorLog,            // shown by `E`
+	pub blame_options: git::BlameOptions,
+	pub highlighter: Option<Arc<Highlighter>>, // shared with the background blame thread
+	pub keymap: Keymap,
+	pub theme: Theme,
+	heatmap: bool,                         // toggled by `a`
+	dim: bool,                             // toggled by `T`
+	pub layouts: Vec<ColumnLayout>,        // cycled through by `L`
+	layout: Option<usize>,                 // the last one chosen, until a column is toggled on its own
+	pub dim_config: DimConfig,             // from the config, for `T`
+	clipboard: Option<arboard::Clipboard>, // kept alive because X11 clipboards are served by the owning process
+	loading: Option<Loading>,
+	history: Option<History>,         // `w` running in the background, cancelled by `q`
+	followed: Option<FollowedLines>,  // what `w` last followed, so `P` can follow it again
+	pub log_options: git::LogOptions, // first_parent is toggled by `P`
+	pub head: Option<Oid>,            // HEAD's commit when blaming at it without a rev, so ctrl-r can follow it
+	pub page_scroll: PageScroll,      // how far `d` and `u` move
+	pub regex_search: bool,           // whether `/` queries without a \v or \V prefix are regexes
+	pub wrap_scroll: bool,            // whether `j` and `k` go around the ends of the blame
+	pub abbrev: usize,                // fewest hex digits of shas; line_format.abbrev adds what's needed to disambiguate
+	folded: bool,                     // toggled by `z`
+	wrap: bool,                       // toggled by `r`
+	center: bool,                     // toggled by `Z`
+	minimap: bool,                    // toggled by `M`
+	split: u16,                       // percent of the width the blame gets beside another pane, moved by `<` and `>`
+	range_start: Option<usize>,       // where `v` started selecting, for `w` on several lines
+	expanded_folds: HashSet<usize>,   // first lines of folds opened by <enter>
+	fold_state: ListState,            // the rows actually rendered while folded
+	blame_cache: LruCache<BlameKey, Vec<git::BlameHunk>>, // blames left by `b` and `B`, for going back to them
+}
+
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(2);
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+const BLAME_CACHE_SIZE: usize = 16;
+const SUMMARY_WIDTHS: [usize; 3] = [20, 40, 60];
+const ERROR_LOG_SIZE: usize = 100;
+const SPLIT_STEP: u16 = 10; // percent of the width `<` and `>` move the split
+const SPLIT_RANGE: (u16, u16) = (20, 80);
+const PINNED_HEIGHT: u16 = 2; // the followed line above its history, and a border under it
+
+// a blame is only reused with the options it was made with
+type BlameKey = (Oid, PathBuf, git::BlameOptions);
+
+// the initial blame, running in the background so the UI comes up right away and fills in as git goes
+struct Loading {
+	stream: git::BlameStream,
+	started: Instant,
+	select_line: Option<usize>,
+	select_index: Option<usize>, // for `b`, which keeps the selection where it was
+	selected: bool,              // once the code has come, so moving while git runs isn't undone
+}
+
+// every error shown so far, so one dismissed too quickly can be read again
+#[derive(Default)]
+struct ErrorLog {
+	entries: VecDeque<(SystemTime, String)>,
+}
+
+impl ErrorLog {
+	fn push(&mut self, time: SystemTime, error: String) {
+		if self.entries.len() == ERROR_LOG_SIZE {
+			self.entries.pop_front();
+		}
+		self.entries.push_back((time, error));
+	}
+
+	fn text(&self) -> Text<'static> {
+		if self.entries.is_empty() {
+			return "no errors".into();
+		}
+		let lines: Vec<Line> = self
+			.entries
+			.iter()
+			.map(|(time, error)| {
+				let time = chrono::DateTime::<chrono::Local>::from(*time).format("%H:%M:%S");
+				Line::from(vec![
+					Span::styled(time.to_string(), Style::default().fg(Color::DarkGray)),
+					Span::raw(format!(" {}", error)),
+				])
+			})
+			.collect();
+		lines.into()
+	}
+}
+
+struct History {
+	follow: git::LogFollow,
+	started: Instant,
+	in_hunk: bool, // whether the last chunk ended in a diff hunk, for highlighting the next
+}
+
+#[derive(Clone)]
+struct FollowedLines {
+	path: PathBuf,
+	start: usize, // 0-based, inclusive
+	end: usize,
+	commit: Oid,
+	pinned: Line<'static>, // the first line as the blame had it, shown above the history
 }
 
+#[derive(Default)]
 struct Search {
 	editing: bool,
 	query: String,
+	origin: (Option<usize>, u16), // the selection and panel scroll from before `/`, where esc goes back to
+	regex: Option<Result<Regex, String>>, // set by compile when the query is a regex
+}
+
+impl Search {
+	// a \v prefix makes the rest of the query a regex and \V makes it a substring, whatever regex_search says
+	fn compile(&mut self, regex_search: bool) {
+		let (regex, pattern) = split_query(&self.query, regex_search);
+		self.regex = regex.then(|| {
+			RegexBuilder::new(pattern)
+				.case_insensitive(!pattern.chars().any(char::is_uppercase))
+				.build()
+				// the last line of a syntax error is the message, the rest point at where it is
+				.map_err(|e| {
+					e.to_string()
+						.lines()
+						.last()
+						.unwrap_or_default()
+						.trim_start_matches("error: ")
+						.to_owned()
+				})
+		});
+	}
+
+	fn invalid(&self) -> Option<&str> {
+		match &self.regex {
+			Some(Err(e)) => Some(e),
+			_ => None,
+		}
+	}
+
+	// the code, or the sha or author where the line shows them. each is searched on its own, so this doesn't have to
+	// render the line
+	fn matches(&self, hunk: &git::BlameHunk) -> bool {
+		self.matches_text(&hunk.code_text())
+			|| hunk.first_in_hunk && (self.matches_text(&hunk.author) || self.matches_text(&hunk.commit.to_string()))
+	}
+
+	fn matches_line(&self, line: &Line) -> bool {
+		let text: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+		self.matches_text(&text)
+	}
+
+	fn matches_author(&self, hunk: &git::BlameHunk) -> bool {
+		hunk.matches_author(&self.query)
+	}
+
+	fn matches_text(&self, text: &str) -> bool {
+		let (_, pattern) = split_query(&self.query, false);
+		match &self.regex {
+			_ if pattern.is_empty() => false,
+			Some(Ok(regex)) => regex.is_match(text),
+			Some(Err(_)) => false,
+			None => git::smartcase_contains(text, pattern),
+		}
+	}
+}
+
+// whether query is a regex, and query without its \v or \V prefix
+fn split_query(query: &str, regex_search: bool) -> (bool, &str) {
+	if let Some(pattern) = query.strip_prefix("\\v") {
+		(true, pattern)
+	} else if let Some(pattern) = query.strip_prefix("\\V") {
+		(false, pattern)
+	} else {
+		(regex_search, query)
+	}
+}
+
+// set by `m`, only for the blame they were set in
+#[derive(Default)]
+struct Marks {
+	blame: Option<(Oid, PathBuf)>,
+	lines: HashMap<char, usize>, // indexes into blame
+}
+
+impl Marks {
+	// forgets the marks from a different commit or file
+	fn for_blame(&mut self, commit_path: &CommitPath) -> &mut HashMap<char, usize> {
+		let blame = (commit_path.commit, commit_path.path.to_owned());
+		if self.blame.as_ref() != Some(&blame) {
+			self.blame = Some(blame);
+			self.lines.clear();
+		}
+		&mut self.lines
+	}
+}
+
+struct FilePicker {
+	files: Vec<String>,
+	query: String,
+	state: ListState, // selection within the files matching query
+}
+
+// commit_stack from the bottom, one line per entry
+struct StackPicker {
+	entries: Vec<Line<'static>>,
+	state: ListState,
+}
+
+// the commits that changed the file, from git::FileLog
+struct LogPicker {
+	loading: Option<git::FileLog>, // until git log finishes, with no commits yet
+	started: Instant,
+	commits: Vec<git::FileCommit>,
+	entries: Vec<Line<'static>>,
+	state: ListState,
+}
+
+impl FilePicker {
+	fn matches(&self) -> Vec<&str> {
+		self.files
+			.iter()
+			.filter(|file| fuzzy_match(&self.query, file))
+			.map(|file| file.as_str())
+			.collect()
+	}
+}
+
+// query's chars appear in order in candidate, with smartcase like Search
+fn fuzzy_match(query: &str, candidate: &str) -> bool {
+	let case_sensitive = query.chars().any(char::is_uppercase);
+	let mut candidate = candidate.chars();
+	query.chars().all(|q| {
+		candidate.any(|c| {
+			if case_sensitive {
+				c == q
+			} else {
+				c.to_lowercase().eq(q.to_lowercase())
+			}
+		})
+	})
+}
+
+// a row of the blame list while folded
+#[derive(Debug, PartialEq, Eq)]
+enum Row {
+	Line(usize),
+	Fold { start: usize, len: usize },
+}
+
+impl Row {
+	fn first(&self) -> usize {
+		match self {
+			Row::Line(index) => *index,
+			Row::Fold { start, .. } => *start,
+		}
+	}
+
+	fn last(&self) -> usize {
+		match self {
+			Row::Line(index) => *index,
+			Row::Fold { start, len } => start + len - 1,
+		}
+	}
+
+	fn contains(&self, index: usize) -> bool {
+		match self {
+			Row::Line(i) => *i == index,
+			Row::Fold { start, len } => (*start..start + len).contains(&index),
+		}
+	}
+}
+
+// collapses runs of consecutive lines from the same commit unless they were expanded
+fn fold_rows(blame: &[git::BlameHunk], expanded: &HashSet<usize>) -> Vec<Row> {
+	let mut rows = vec![];
+	let mut start = 0;
+	while start < blame.len() {
+		let len = blame[start..]
+			.iter()
+			.take_while(|line| line.commit == blame[start].commit)
+			.count();
+		if len > 1 && !expanded.contains(&start) {
+			rows.push(Row::Fold { start, len });
+		} else {
+			rows.extend((start..start + len).map(Row::Line));
+		}
+		start += len;
+	}
+	rows
+}
+
+// the first line of the next or previous run of lines from one commit, the same runs that fold
+fn commit_boundary(blame: &[git::BlameHunk], index: usize, forward: bool) -> Option<usize> {
+	let mut starts = fold_rows(blame, &HashSet::new()).into_iter().map(|row| row.first());
+	match forward {
+		true => starts.find(|&start| start > index),
+		false => starts.take_while(|&start| start < index).last(),
+	}
+}
+
+// like commit_boundary, but for runs of lines by one author, which can span several commits
+fn author_boundary(blame: &[git::BlameHunk], index: usize, forward: bool) -> Option<usize> {
+	let mut starts = (0..blame.len()).filter(|&i| i == 0 || blame[i].author_email != blame[i - 1].author_email);
+	match forward {
+		true => starts.find(|&start| start > index),
+		false => starts.take_while(|&start| start < index).last(),
+	}
+}
+
+fn row_of(rows: &[Row], index: Option<usize>) -> Option<usize> {
+	index.and_then(|index| rows.iter().position(|row| row.contains(index)))
+}
+
+enum QueryEdit {
+	Editing,
+	Cancelled,
+	Submitted,
+}
+
+// handles a key press while typing a search or filter
+fn edit_query(query: &mut String, key: &KeyEvent) -> QueryEdit {
+	match key {
+		KeyEvent { code: KeyCode::Esc, .. }
+		| KeyEvent {
+			code: Char('c'),
+			modifiers: KeyModifiers::CONTROL,
+			..
+		} => return QueryEdit::Cancelled,
+		KeyEvent {
+			code: Char('u'),
+			modifiers: KeyModifiers::CONTROL,
+			..
+		} => query.clear(),
+		KeyEvent { code: Char(c), .. } => query.push(*c),
+		KeyEvent {
+			code: KeyCode::Backspace,
+			..
+		} => {
+			query.pop();
+		}
+		KeyEvent {
+			code: KeyCode::Enter, ..
+		} => return QueryEdit::Submitted,
+		_ => {} // ignored
+	}
+	QueryEdit::Editing
+}
+
+// typed after `R` to blame at that revision, or after `D` to compare with it
+struct RevPrompt {
+	query: String,
+	compare: bool,
+}
+
+// the same file blamed at another revision, next to the main blame
+struct Compare {
+	commit: Oid,
+	merge_base: Option<Oid>, // with the main blame's commit
+	blame: Vec<git::BlameHunk>,
+	state: ListState,
+	focused: bool, // toggled by <tab>; movement keys go to the focused list
+	loading: Option<git::BlameStream>,
+}
+
+struct ParentPrompt {
+	index: usize,
+	parents: Vec<Oid>,
+}
+
+struct RightPanel {
+	kind: PanelKind,
+	text: Text<'static>,
+	search_match: Option<usize>, // line of the last search match, so n moves past it even when scrolling is clamped
+	pinned: Option<Line<'static>>, // above the text, outside of its scrolling
+}
+
+impl RightPanel {
+	fn new(kind: PanelKind, text: Text<'static>) -> RightPanel {
+		RightPanel {
+			kind,
+			text,
+			search_match: None,
+			pinned: None,
+		}
+	}
+
+	fn max_scroll(&self, term_size: &Rect) -> u16 {
+		let height = match self.pinned {
+			Some(_) => term_size.height.saturating_sub(PINNED_HEIGHT),
+			None => term_size.height,
+		};
+		u16::try_from(self.text.height()).unwrap().saturating_sub(height)
+	}
+}
+
+#[derive(PartialEq, Eq)]
+enum PanelKind {
+	Show,
+	LineHistory,
+	FileDiff,
+	CommitDetails,
+	AuthorStats,
 }
 
 struct CommitPath {
 	commit: Oid,
 	path: PathBuf,
+	view: ListState, // selection and scroll offset saved when `b` leaves this entry
 }
 
 impl App<'_> {
@@ -51,97 +486,992 @@ impl App<'_> {
 		App {
 			blame: vec![],
 			blame_state: ListState::default(),
+			blame_area: Rect::default(),
+			minimap_area: Rect::default(),
+			item_heights: vec![],
 			repo,
 			commit_stack: vec![CommitPath {
 				commit,
 				path: rel_path.to_owned(),
+				view: ListState::default(),
 			}],
 			right_panel: None,
 			line_history_scroll: 0,
+			line_format: render::LineFormat::default(),
+			line_numbers: true,
+			commit_details: None,
 			popup: None,
+			parent_prompt: None,
+			file_picker: None,
+			stack_picker: None,
+			log_picker: None,
 			search: None,
+			author_filter: None,
 			line_number: None,
+			marks: Marks::default(),
+			pending_mark: None,
+			pending_edit: None,
+			rev_prompt: None,
+			followed: None,
+			log_options: git::LogOptions::default(),
+			head: None,
+			page_scroll: PageScroll::default(),
+			regex_search: false,
+			wrap_scroll: false,
+			abbrev: git::DEFAULT_ABBREV,
+			compare: None,
+			message: None,
+			errors: ErrorLog::default(),
+			blame_options: git::BlameOptions::default(),
+			highlighter: None,
+			keymap: Keymap::default(),
+			theme: Theme::default(),
+			heatmap: false,
+			dim: false,
+			dim_config: DimConfig::default(),
+			layouts: config::default_layouts(),
+			layout: None,
+			clipboard: None,
+			loading: None,
+			history: None,
+			folded: false,
+			wrap: false,
+			center: false,
+			minimap: false,
+			split: 50,
+			range_start: None,
+			expanded_folds: HashSet::new(),
+			fold_state: ListState::default(),
+			blame_cache: LruCache::new(NonZeroUsize::new(BLAME_CACHE_SIZE).unwrap()),
+		}
+	}
+
+	// selects a 1-based line number, clamping to the lines that were blamed
+	pub fn select_line(&mut self, line_num: usize) {
+		let Some(last) = self.blame.last() else {
+			return;
+		};
+		let last = last.line_num as usize;
+		if line_num > last {
+			self.message = Some(format!("line {} is past the end, showing line {}", line_num, last));
+		}
+		self.blame_state
+			.select(Some(self.line_index(line_num).unwrap_or(self.blame.len() - 1)));
+	}
+
+	// index of the first line at or after a 1-based line number, which differs from line_num - 1 for a range
+	fn line_index(&self, line_num: usize) -> Option<usize> {
+		self.blame.iter().position(|line| line.line_num as usize >= line_num)
+	}
+
+	// starts blaming the top of commit_stack on another thread; run_app picks up the result
+	pub fn blame_in_background(&mut self, select_line: Option<usize>) {
+		let commit_path = self.commit_stack.last().unwrap();
+		match self.spawn_blame(commit_path.commit, &commit_path.path) {
+			Ok(stream) => {
+				self.loading = Some(Loading {
+					stream,
+					started: Instant::now(),
+					select_line,
+					select_index: None,
+					selected: false,
+				})
+			}
+			Err(e) => self.show_error(e.to_string()),
+		}
+	}
+
+	// blames path at commit in the background with the current options
+	fn spawn_blame(&self, commit: Oid, path: &Path) -> Result<git::BlameStream, Box<dyn Error>> {
+		git::BlameStream::spawn(self.repo, path, commit, &self.blame_options, self.highlighter.clone())
+	}
+
+	// line_format plus what depends on the blame and the other toggles
+	fn display_format(&self) -> render::LineFormat {
+		render::LineFormat {
+			gutter_width: if self.line_numbers {
+				self.blame.last().map_or(0, |line| line.line_num).to_string().len()
+			} else {
+				0
+			},
+			gutter_color: self.theme.gutter,
+			..self.line_format
+		}
+	}
+
+	// the blame as shown, minus horizontal scrolling and lines hidden by folds or the author filter
+	fn visible_text(&self) -> String {
+		let line_format = render::LineFormat {
+			code_scroll: 0,
+			..self.display_format()
+		};
+		let rows = navigable_rows(self).unwrap_or_else(|| (0..self.blame.len()).map(Row::Line).collect());
+		let mut text = String::new();
+		for row in rows {
+			let line = match row {
+				Row::Line(index) => self.blame[index].to_line(&line_format),
+				Row::Fold { start, len } => self.blame[start].to_fold_line(&line_format, len),
+			};
+			let line: String = line.spans.iter().map(|span| span.content.as_ref()).collect();
+			text.push_str(line.trim_end());
+			text.push('\n');
+		}
+		text
+	}
+
+	pub fn prefs(&self) -> Prefs {
+		Prefs {
+			line_numbers: Some(self.line_numbers),
+			ignore_whitespace: Some(self.blame_options.ignore_whitespace),
+			absolute_dates: Some(self.line_format.absolute_dates),
+			heatmap: Some(self.heatmap),
+			dim: Some(self.dim),
+			folded: Some(self.folded),
+			wrap: Some(self.wrap),
+			center: Some(self.center),
+			summary_width: Some(self.line_format.summary_width),
+			author_badges: Some(self.line_format.author_badges),
+			sha_column: Some(self.line_format.sha),
+			author_column: Some(self.line_format.author),
+			date_column: Some(self.line_format.date),
+			minimap: Some(self.minimap),
+		}
+	}
+
+	// before blaming, since ignore_whitespace changes the blame
+	pub fn apply_prefs(&mut self, prefs: &Prefs) {
+		let Prefs {
+			line_numbers,
+			ignore_whitespace,
+			absolute_dates,
+			heatmap,
+			dim,
+			folded,
+			wrap,
+			center,
+			summary_width,
+			author_badges,
+			sha_column,
+			author_column,
+			date_column,
+			minimap,
+		} = *prefs;
+		self.line_numbers = line_numbers.unwrap_or(self.line_numbers);
+		self.blame_options.ignore_whitespace = ignore_whitespace.unwrap_or(self.blame_options.ignore_whitespace);
+		self.line_format.absolute_dates = absolute_dates.unwrap_or(self.line_format.absolute_dates);
+		self.heatmap = heatmap.unwrap_or(self.heatmap);
+		self.dim = dim.unwrap_or(self.dim);
+		self.folded = folded.unwrap_or(self.folded);
+		self.wrap = wrap.unwrap_or(self.wrap);
+		self.center = center.unwrap_or(self.center);
+		self.line_format.summary_width = summary_width.unwrap_or(self.line_format.summary_width);
+		self.line_format.author_badges = author_badges.unwrap_or(self.line_format.author_badges);
+		self.line_format.sha = sha_column.unwrap_or(self.line_format.sha);
+		self.line_format.author = author_column.unwrap_or(self.line_format.author);
+		self.line_format.date = date_column.unwrap_or(self.line_format.date);
+		self.minimap = minimap.unwrap_or(self.minimap);
+	}
+
+	// takes what the background blames have found so far
+	fn check_loading(&mut self) {
+		self.check_compare();
+		let Some(loading) = &mut self.loading else {
+			return;
+		};
+		let result = loading.stream.poll(&mut self.blame);
+		if !loading.selected && !self.blame.is_empty() {
+			loading.selected = true;
+			let (select_line, select_index) = (loading.select_line, loading.select_index);
+			match select_line {
+				Some(line) => self.select_line(line),
+				None => self.reselect(select_index),
+			}
+		}
+		match result {
+			None => {}
+			Some(Ok(())) => {
+				self.loading = None;
+				self.blame_reloaded();
+			}
+			Some(Err(e)) => {
+				self.loading = None;
+				self.blame.clear(); // not blamed after all
+				self.show_error(e);
+			}
+		}
+	}
+
+	fn check_compare(&mut self) {
+		let Some(compare) = &mut self.compare else {
+			return;
+		};
+		let Some(stream) = &mut compare.loading else {
+			return;
+		};
+		let result = stream.poll(&mut compare.blame);
+		let selected = compare.state.selected().unwrap_or(0);
+		compare
+			.state
+			.select(Some(selected.min(compare.blame.len().saturating_sub(1))));
+		match result {
+			None => {}
+			Some(Ok(())) => compare.loading = None,
+			Some(Err(e)) => {
+				self.compare = None;
+				self.show_error(e);
+			}
 		}
 	}
+
+	// fills in the file history once git log finishes
+	fn check_file_log(&mut self) {
+		let Some(picker) = &mut self.log_picker else {
+			return;
+		};
+		let Some(result) = picker.loading.as_mut().and_then(|log| log.poll()) else {
+			return;
+		};
+		picker.loading = None;
+		let top = self.commit_stack.last().unwrap();
+		match result {
+			Ok(commits) if commits.is_empty() => {
+				self.message = Some(format!("no commits have changed {}", top.path.display()));
+				self.log_picker = None;
+			}
+			Ok(commits) => {
+				picker.entries = commits
+					.iter()
+					.map(|file_commit| render::file_commit_line(file_commit, &self.line_format, &top.path))
+					.collect();
+				let current = commits.iter().position(|file_commit| file_commit.commit == top.commit);
+				picker.state.select(Some(current.unwrap_or(0)));
+				picker.commits = commits;
+			}
+			Err(e) => {
+				self.log_picker = None;
+				self.show_error(e);
+			}
+		}
+	}
+
+	fn show_error(&mut self, error: String) {
+		self.errors.push(SystemTime::now(), error.clone());
+		self.popup = Some(error.into());
+	}
+
+	// adds more line history while it's scrolled near the end, or gives up on it if the panel now shows something else
+	fn check_history(&mut self, term_size: &Rect) {
+		let Some(history) = &mut self.history else {
+			return;
+		};
+		match &mut self.right_panel {
+			Some(panel) if panel.kind == PanelKind::LineHistory => {
+				// a screen past the bottom, so scrolling rarely has to wait
+				let wanted = usize::from(self.line_history_scroll) + 2 * usize::from(term_size.height);
+				while panel.text.height() < wanted {
+					match history.follow.poll() {
+						git::FollowOutput::Pending => break,
+						git::FollowOutput::More(more) => {
+							let more = render::ansi_text(more);
+							let path = self.followed.as_ref().map(|lines| lines.path.as_path());
+							panel.text.extend(match (&self.highlighter, path) {
+								(Some(highlighter), Some(path)) => {
+									render::highlight_history(more, path, highlighter, &mut history.in_hunk)
+								}
+								_ => more,
+							})
+						}
+						git::FollowOutput::Done(error) => {
+							if let Some(error) = error {
+								panel.text = error.into();
+							}
+							self.history = None;
+							break;
+						}
+					}
+				}
+			}
+			_ => self.history = None,
+		}
+	}
+
+	// shows the lines' history in the right panel once git log finishes
+	fn follow_lines(&mut self, lines: FollowedLines) -> Result<(), Box<dyn Error>> {
+		let follow = git::LogFollow::spawn(
+			self.repo,
+			&lines.path,
+			lines.start,
+			lines.end,
+			lines.commit,
+			&self.log_options,
+		)?;
+		self.history = Some(History {
+			follow,
+			started: Instant::now(),
+			in_hunk: false,
+		});
+		let mut panel = RightPanel::new(PanelKind::LineHistory, Text::default());
+		panel.pinned = Some(lines.pinned.clone());
+		self.followed = Some(lines);
+		self.right_panel = Some(panel);
+		self.line_history_scroll = 0;
+		Ok(())
+	}
+
+	// blames the selected line's file at one of its commit's parents
+	fn blame_parent(&mut self, index: usize, parent: Oid) -> Result<(), Box<dyn Error>> {
+		let line_path = match self.blame[index].path.to_owned() {
+			Some(p) => p,
+			None => self.commit_stack.last().unwrap().path.to_owned(),
+		};
+		self.push_blame(parent, line_path, Some(index))
+	}
+
+	// blames path at commit on top of commit_stack, so `B` comes back to the current blame
+	fn push_blame(&mut self, commit: Oid, path: PathBuf, select: Option<usize>) -> Result<(), Box<dyn Error>> {
+		if self.loading.is_some() {
+			return Err("still loading the blame".into());
+		}
+		let cached = self.blame_cache.pop(&self.blame_key(commit, &path));
+		// a blame that isn't cached runs in the background, like the first one, so the spinner shows
+		let in_background = cached.is_none() && git::file_exists(self.repo, commit, &path)?;
+		// still push an empty blame so the title says where we are and `B` goes back
+		let blame = cached.unwrap_or_default();
+		self.commit_stack.last_mut().unwrap().view = self.blame_state.clone();
+		let top = self.commit_stack.last().unwrap();
+		let key = self.blame_key(top.commit, &top.path);
+		self.blame_cache.put(key, mem::replace(&mut self.blame, blame));
+		self.commit_stack.push(CommitPath {
+			commit,
+			path,
+			view: ListState::default(),
+		});
+		if in_background {
+			self.blame_state = ListState::default();
+			self.blame_in_background(None);
+			if let Some(loading) = &mut self.loading {
+				loading.select_index = select;
+			}
+		} else {
+			self.reselect(select);
+			self.blame_reloaded();
+		}
+		Ok(())
+	}
+
+	// like git, rev can be a branch, tag, short sha, or something like HEAD~3
+	fn blame_rev(&mut self, rev: &str) -> Result<(), Box<dyn Error>> {
+		let commit = self.repo.revparse_single(rev)?.peel_to_commit()?.id();
+		let path = self.commit_stack.last().unwrap().path.to_owned();
+		self.push_blame(commit, path, self.blame_state.selected())
+	}
+
+	fn compare_rev(&mut self, rev: &str) -> Result<(), Box<dyn Error>> {
+		let commit = self.repo.revparse_single(rev)?.peel_to_commit()?.id();
+		let path = &self.commit_stack.last().unwrap().path;
+		if !git::file_exists(self.repo, commit, path)? {
+			return Err(format!(
+				"{} doesn't exist at {:.*}",
+				path.display(),
+				self.line_format.abbrev,
+				commit
+			)
+			.into());
+		}
+		self.compare = Some(Compare {
+			commit,
+			merge_base: None,
+			blame: vec![],
+			state: ListState::default().with_selected(Some(0)),
+			focused: false,
+			loading: None,
+		});
+		self.right_panel = None;
+		self.update_merge_base();
+		self.reblame_compare();
+		Ok(())
+	}
+
+	// blames the comparison again in the background, after it opens or the blame options change
+	fn reblame_compare(&mut self) {
+		let Some(commit) = self.compare.as_ref().map(|compare| compare.commit) else {
+			return;
+		};
+		let path = &self.commit_stack.last().unwrap().path;
+		match self.spawn_blame(commit, path) {
+			Ok(stream) => self.compare.as_mut().unwrap().loading = Some(stream),
+			Err(e) => {
+				self.compare = None;
+				self.show_error(e.to_string());
+			}
+		}
+	}
+
+	// after either side of the comparison changes commits
+	fn update_merge_base(&mut self) {
+		let commit = self.commit_stack.last().unwrap().commit;
+		if let Some(compare) = &mut self.compare {
+			compare.merge_base = self.repo.merge_base(commit, compare.commit).ok();
+		}
+	}
+
+	// re-blame at the top of commit_stack, keeping the selection where possible
+	fn reblame(&mut self) -> Result<(), Box<dyn Error>> {
+		if self.loading.is_some() {
+			return Err("still loading the blame".into());
+		}
+		let commit_path = self.commit_stack.last().unwrap();
+		let (commit, path) = (commit_path.commit, commit_path.path.to_owned());
+		let blame = self.take_blame(commit, &path)?;
+		self.replace_blame(blame);
+		Ok(())
+	}
+
+	// swaps in a new blame of the top of commit_stack, returning the old one
+	fn replace_blame(&mut self, blame: Vec<git::BlameHunk>) -> Vec<git::BlameHunk> {
+		let old = mem::replace(&mut self.blame, blame);
+		self.reselect(self.blame_state.selected());
+		self.blame_reloaded();
+		self.reblame_compare();
+		old
+	}
+
+	// blames the top of commit_stack again, or the new HEAD if it was HEAD and has since moved
+	fn reload(&mut self) -> Result<String, Box<dyn Error>> {
+		if self.loading.is_some() {
+			return Err("still loading the blame".into());
+		}
+		let top = self.commit_stack.last_mut().unwrap();
+		let mut moved = None;
+		if self.head.is_some_and(|head| head == top.commit) {
+			let head = self.repo.head()?.peel_to_commit()?.id();
+			if head != top.commit {
+				top.commit = head;
+				self.head = Some(head);
+				moved = Some(head);
+			}
+		}
+		let key = (top.commit, top.path.clone());
+		self.blame_cache.pop(&self.blame_key(key.0, &key.1));
+		self.commit_details = None;
+		self.reblame()?;
+		Ok(match moved {
+			Some(head) => format!("reloaded at HEAD, now {:.*}", self.line_format.abbrev, head),
+			None => "reloaded".to_owned(),
+		})
+	}
+
+	// pops commit_stack down to len entries, caching the blame being left
+	fn pop_blame_to(&mut self, len: usize) -> Result<(), Box<dyn Error>> {
+		if self.loading.is_some() {
+			return Err("still loading the blame".into());
+		}
+		// blame what's left on top first, so a failure leaves the stack and the blame as they were
+		let top = &self.commit_stack[len - 1];
+		let (commit, path, view) = (top.commit, top.path.to_owned(), top.view.clone());
+		let blame = self.take_blame(commit, &path)?;
+		let popped = self.commit_stack.pop().unwrap();
+		self.commit_stack.truncate(len);
+		self.blame_state = view;
+		let old = self.replace_blame(blame);
+		self.blame_cache.put(self.blame_key(popped.commit, &popped.path), old);
+		Ok(())
+	}
+
+	fn stack_entries(&self) -> Vec<Line<'static>> {
+		self.commit_stack
+			.iter()
+			.enumerate()
+			.map(|(i, commit_path)| {
+				let summary = match self.repo.find_commit(commit_path.commit) {
+					Ok(commit) => commit.summary().unwrap_or_default().to_owned(),
+					Err(_) => "working tree".to_owned(),
+				};
+				Line::from(vec![
+					Span::styled(format!("{:2} ", i), Style::default().fg(self.theme.gutter)),
+					Span::styled(
+						format!("{:.*}", self.line_format.abbrev, commit_path.commit),
+						Style::default().fg(Color::Yellow),
+					),
+					Span::styled(
+						format!(" {}", commit_path.path.display()),
+						Style::default().fg(Color::LightBlue),
+					),
+					Span::raw(format!(" {}", summary)),
+				])
+			})
+			.collect()
+	}
+
+	fn blame_key(&self, commit: Oid, path: &Path) -> BlameKey {
+		(commit, path.to_owned(), self.blame_options.clone())
+	}
+
+	// removes a blame from the cache, or runs git blame if it isn't there
+	fn take_blame(&mut self, commit: Oid, path: &Path) -> Result<Vec<git::BlameHunk>, Box<dyn Error>> {
+		match self.blame_cache.pop(&self.blame_key(commit, path)) {
+			Some(blame) => Ok(blame),
+			None => git::blame(
+				self.repo,
+				path,
+				commit,
+				&self.blame_options,
+				self.highlighter.as_deref(),
+			),
+		}
+	}
+
+	fn active_author_filter(&self) -> Option<&Search> {
+		self.author_filter.as_ref().filter(|filter| !filter.query.is_empty())
+	}
+
+	// blames another file at the current commit, starting a new commit_stack
+	fn open_file(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+		if self.loading.is_some() {
+			return Err("still loading the blame".into());
+		}
+		let commit = self.commit_stack.last().unwrap().commit;
+		self.blame = self.take_blame(commit, path)?;
+		self.commit_stack = vec![CommitPath {
+			commit,
+			path: path.to_owned(),
+			view: ListState::default(),
+		}];
+		self.compare = None; // of the old file
+		self.blame_state = ListState::default();
+		self.line_format.code_scroll = 0;
+		self.blame_reloaded();
+		Ok(())
+	}
+
+	// refreshes any panel derived from the whole blame
+	fn blame_reloaded(&mut self) {
+		let commits = self.blame.iter().map(|line| line.commit).collect::<HashSet<_>>();
+		let title_commit = self.commit_stack.last().unwrap().commit;
+		self.line_format.abbrev =
+			git::unambiguous_abbrev(self.repo, commits.into_iter().chain([title_commit]), self.abbrev);
+		self.update_merge_base();
+		self.expanded_folds.clear();
+		self.range_start = None;
+		if let Some(panel) = &mut self.right_panel {
+			if panel.kind == PanelKind::AuthorStats {
+				panel.text = author_stats_text(&self.blame);
+			}
+		}
+	}
+
+	// shows just the layout's columns, keeping the summary's width if it's already shown
+	fn apply_layout(&mut self, index: usize) {
+		let columns = &self.layouts[index].columns;
+		self.line_numbers = columns.contains(&Column::LineNumbers);
+		self.line_format.author_badges = columns.contains(&Column::Badges);
+		self.line_format.sha = columns.contains(&Column::Sha);
+		self.line_format.author = columns.contains(&Column::Author);
+		self.line_format.date = columns.contains(&Column::Date);
+		self.line_format.summary_width = match (columns.contains(&Column::Summary), self.line_format.summary_width) {
+			(false, _) => 0,
+			(true, 0) => SUMMARY_WIDTHS[1],
+			(true, width) => width,
+		};
+		self.layout = Some(index);
+	}
+
+	// whether `T` dims line, by how old its commit is as of now
+	fn dims(&self, line: &git::BlameHunk, now: SystemTime) -> bool {
+		let threshold = Duration::from_secs(self.dim_config.days * 24 * 60 * 60);
+		let older = now.duration_since(line.commit_time).unwrap_or_default() > threshold;
+		self.dim && older != self.dim_config.newer
+	}
+
+	// the commit, line numbers, and code of the first of the lines
+	fn pinned_line(&self, first: usize, last: usize) -> Line<'static> {
+		let line = &self.blame[first];
+		let line_nums = match first == last {
+			true => line.line_num.to_string(),
+			false => format!("{}-{}", line.line_num, self.blame[last].line_num),
+		};
+		let mut spans = vec![
+			Span::styled(
+				format!("{:.*}", self.line_format.abbrev, line.commit),
+				Style::default().fg(Color::Yellow),
+			),
+			Span::styled(format!(" {} ", line_nums), Style::default().fg(self.theme.gutter)),
+		];
+		spans.extend(render::code_spans(&line.code));
+		Line::from(spans)
+	}
+
+	// the first and last index of the lines selected by `v`, in order
+	fn selected_range(&self) -> Option<(usize, usize)> {
+		let (start, selected) = (self.range_start?, self.blame_state.selected()?);
+		Some((start.min(selected), start.max(selected)))
+	}
+
+	// clamps a selection carried over from before the blame was reloaded
+	fn reselect(&mut self, index: Option<usize>) {
+		self.blame_state.select(clamp_selection(index, self.blame.len()));
+	}
 }
 
 type CrosstermTerm = Terminal<CrosstermBackend<Stdout>>;
 
 pub fn setup() -> Result<CrosstermTerm, Box<dyn Error>> {
+	// restore the terminal before the panic message is printed, or it's lost in the alternate screen.
+	// a panic on the blame thread is reported by check_loading instead, with the UI still running
+	let default_hook = panic::take_hook();
+	panic::set_hook(Box::new(move |info| {
+		if thread::current().name() == Some("main") {
+			_ = disable_raw_mode();
+			_ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, cursor::Show);
+		}
+		default_hook(info);
+	}));
 	enable_raw_mode()?;
 	let mut stdout = io::stdout();
-	execute!(stdout, EnterAlternateScreen)?;
+	execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 	let backend = CrosstermBackend::new(stdout);
 	Ok(Terminal::new(backend)?)
 }
 
 pub fn teardown(terminal: &mut CrosstermTerm) {
 	_ = disable_raw_mode();
-	_ = execute!(terminal.backend_mut(), LeaveAlternateScreen);
+	_ = execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture);
 	_ = terminal.show_cursor();
 }
 
-pub fn run_app(terminal: &mut CrosstermTerm, mut app: App) -> Result<(), Box<dyn Error>> {
+pub fn run_app(terminal: &mut CrosstermTerm, app: &mut App) -> Result<(), Box<dyn Error>> {
 	loop {
-		terminal.draw(|frame| ui(frame, &mut app))?;
-		if let Event::Key(key) = event::read()? {
-			match handle_input(&key, &mut app, &terminal.size()?) {
+		app.check_loading();
+		app.check_history(&terminal.size()?);
+		app.check_file_log();
+		terminal.draw(|frame| ui(frame, app))?;
+		let compare_loading = app.compare.as_ref().is_some_and(|compare| compare.loading.is_some());
+		let log_loading = app.log_picker.as_ref().is_some_and(|picker| picker.loading.is_some());
+		if (app.loading.is_some() || app.history.is_some() || compare_loading || log_loading)
+			&& !event::poll(SPINNER_INTERVAL)?
+		{
+			continue; // redraw the spinner
+		}
+		if app.message.is_some() && !event::poll(MESSAGE_TIMEOUT)? {
+			app.message = None;
+			continue;
+		}
+		match event::read()? {
+			Event::Key(key) => match handle_input(&key, app, &terminal.size()?) {
 				Ok(false) => {
 					return Ok(());
 				}
 				Ok(true) => {} // ignored
-				Err(err) => app.popup = Some(format!("{}", err).into()),
+				Err(err) => app.show_error(err.to_string()),
+			},
+			Event::Mouse(mouse) => handle_mouse(&mouse, app, &terminal.size()?),
+			// the loop redraws at the new size
+			Event::Resize(_, _) => clamp_scroll(app, &terminal.size()?),
+			_ => {} // ignored
+		}
+		if let Some((path, line)) = app.pending_edit.take() {
+			let result = run_editor(terminal, &path, line);
+			clamp_scroll(app, &terminal.size()?); // the terminal may have been resized meanwhile
+			match result {
+				// show the edits when blaming the working tree
+				Ok(()) if app.commit_stack.last().unwrap().commit.is_zero() => {
+					if let Err(e) = app.reblame() {
+						app.show_error(e.to_string());
+					}
+				}
+				Ok(()) => {}
+				Err(e) => app.show_error(e.to_string()),
+			}
+		}
+	}
+}
+
+// $VISUAL or $EDITOR +line path, with the UI put away until it exits
+fn run_editor(terminal: &mut CrosstermTerm, path: &Path, line: usize) -> Result<(), Box<dyn Error>> {
+	let editor = ["VISUAL", "EDITOR"]
+		.into_iter()
+		.filter_map(env::var_os)
+		.find(|editor| !editor.is_empty())
+		.ok_or("set $VISUAL or $EDITOR to edit")?;
+	// like git, the editor can have arguments, e.g. "code --wait"
+	let editor = editor.to_string_lossy().into_owned();
+	let mut words = editor.split_whitespace();
+	let mut command = process::Command::new(words.next().unwrap_or_default());
+	command.args(words).arg(format!("+{}", line)).arg(path);
+
+	disable_raw_mode()?;
+	execute!(
+		terminal.backend_mut(),
+		LeaveAlternateScreen,
+		DisableMouseCapture,
+		cursor::Show
+	)?;
+	let status = command.status();
+	enable_raw_mode()?;
+	execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+	terminal.clear()?;
+	match status {
+		Ok(status) if status.success() => Ok(()),
+		Ok(status) => Err(format!("{} exited with {}", editor, status).into()),
+		Err(e) => Err(format!("couldn't run {}: {}", editor, e).into()),
+	}
+}
+
+fn handle_mouse(mouse: &MouseEvent, app: &mut App, term_size: &Rect) {
+	if app.popup.is_some() {
+		return;
+	}
+	match mouse.kind {
+		MouseEventKind::ScrollDown => scroll(app, term_size, 3),
+		MouseEventKind::ScrollUp => scroll(app, term_size, -3),
+		MouseEventKind::Down(MouseButton::Left) if contains(app.minimap_area, mouse) => {
+			let area = app.minimap_area;
+			if mouse.row > area.y {
+				let bar_rows = usize::from(area.height - 1);
+				let index = usize::from(mouse.row - area.y - 1) * app.blame.len() / bar_rows;
+				app.blame_state
+					.select(Some(index.min(app.blame.len().saturating_sub(1))));
+			}
+		}
+		MouseEventKind::Down(MouseButton::Left) => {
+			let area = app.blame_area;
+			// the first row of the list area is the title
+			if mouse.column < area.x || mouse.column >= area.x + area.width || mouse.row <= area.y {
+				return;
+			}
+			let mut y = usize::from(mouse.row - area.y - 1);
+			let mut row = if app.folded {
+				app.fold_state.offset()
+			} else {
+				app.blame_state.offset()
+			};
+			while let Some(&height) = app.item_heights.get(row) {
+				if y < height {
+					break;
+				}
+				y -= height;
+				row += 1;
+			}
+			if row >= app.item_heights.len() {
+				return;
+			}
+			if app.folded {
+				app.blame_state
+					.select(Some(fold_rows(&app.blame, &app.expanded_folds)[row].first()));
+			} else {
+				app.blame_state.select(Some(row));
 			}
 		}
+		_ => {} // ignored
 	}
 }
 
+fn contains(area: Rect, mouse: &MouseEvent) -> bool {
+	(area.x..area.x + area.width).contains(&mouse.column) && (area.y..area.y + area.height).contains(&mouse.row)
+}
+
 // returns whether to continue running the app
 fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Rect) -> Result<bool, Box<dyn Error>> {
+	app.message = None;
+	if let Some(prompt) = app.parent_prompt.take() {
+		app.popup = None;
+		if let KeyEvent { code: Char(c), .. } = key {
+			let choice = c.to_digit(10).and_then(|d| (d as usize).checked_sub(1));
+			if let Some(&parent) = choice.and_then(|i| prompt.parents.get(i)) {
+				app.blame_parent(prompt.index, parent)?;
+			}
+		}
+		return Ok(true);
+	}
 	if app.popup.is_some() {
 		// clear the popup on any key press
 		app.popup = None;
 		return Ok(true);
 	}
+	if let Some(picker) = &mut app.file_picker {
+		let mut chosen = None;
+		match key {
+			KeyEvent { code: KeyCode::Esc, .. }
+			| KeyEvent {
+				code: Char('c'),
+				modifiers: KeyModifiers::CONTROL,
+				..
+			} => app.file_picker = None,
+			KeyEvent {
+				code: Char('u'),
+				modifiers: KeyModifiers::CONTROL,
+				..
+			} => {
+				picker.query.clear();
+				picker.state.select(Some(0));
+			}
+			KeyEvent {
+				code: KeyCode::Down, ..
+			}
+			| KeyEvent {
+				code: Char('n'),
+				modifiers: KeyModifiers::CONTROL,
+				..
+			} => {
+				let len = picker.matches().len();
+				move_selection(&mut picker.state, len, 1);
+			}
+			KeyEvent { code: KeyCode::Up, .. }
+			| KeyEvent {
+				code: Char('p'),
+				modifiers: KeyModifiers::CONTROL,
+				..
+			} => {
+				let len = picker.matches().len();
+				move_selection(&mut picker.state, len, -1);
+			}
+			KeyEvent { code: Char(c), .. } => {
+				picker.query.push(*c);
+				picker.state.select(Some(0));
+			}
+			KeyEvent {
+				code: KeyCode::Backspace,
+				..
+			} => {
+				picker.query.pop();
+				picker.state.select(Some(0));
+			}
+			KeyEvent {
+				code: KeyCode::Enter, ..
+			} => {
+				let matches = picker.matches();
+				chosen = picker.state.selected().and_then(|i| matches.get(i)).map(PathBuf::from);
+			}
+			_ => {} // ignored
+		}
+		if let Some(path) = chosen {
+			app.file_picker = None;
+			app.open_file(&path)?;
+		}
+		return Ok(true);
+	}
 
-	if let Some(search) = &mut app.search {
-		if search.editing {
-			match key {
-				KeyEvent { code: KeyCode::Esc, .. }
-				| KeyEvent {
-					code: Char('c'),
-					modifiers: KeyModifiers::CONTROL,
-					..
-				} => {
-					app.search = None;
+	if let Some(picker) = &mut app.stack_picker {
+		let action = app.keymap.action(key);
+		match (key.code, action) {
+			(KeyCode::Esc, _) | (_, Some(Action::Quit | Action::BlameStack)) => app.stack_picker = None,
+			(KeyCode::Down, _) | (_, Some(Action::ScrollDown)) => {
+				move_selection(&mut picker.state, picker.entries.len(), 1)
+			}
+			(KeyCode::Up, _) | (_, Some(Action::ScrollUp)) => {
+				move_selection(&mut picker.state, picker.entries.len(), -1)
+			}
+			(KeyCode::Enter, _) => {
+				let len = picker.state.selected().map_or(app.commit_stack.len(), |i| i + 1);
+				app.stack_picker = None;
+				if len < app.commit_stack.len() {
+					app.pop_blame_to(len)?;
 				}
-				KeyEvent {
-					code: Char('u'),
-					modifiers: KeyModifiers::CONTROL,
-					..
-				} => {
-					search.query.clear();
+			}
+			_ => {} // ignored
+		}
+		return Ok(true);
+	}
+
+	if let Some(picker) = &mut app.log_picker {
+		let action = app.keymap.action(key);
+		match (key.code, action) {
+			(KeyCode::Esc, _) | (_, Some(Action::Quit | Action::FileLog)) => app.log_picker = None,
+			(KeyCode::Down, _) | (_, Some(Action::ScrollDown)) => {
+				move_selection(&mut picker.state, picker.entries.len(), 1)
+			}
+			(KeyCode::Up, _) | (_, Some(Action::ScrollUp)) => {
+				move_selection(&mut picker.state, picker.entries.len(), -1)
+			}
+			(KeyCode::Enter, _) if picker.loading.is_none() => {
+				let picker = app.log_picker.take().unwrap();
+				if let Some(file_commit) = picker.state.selected().and_then(|i| picker.commits.into_iter().nth(i)) {
+					app.push_blame(file_commit.commit, file_commit.path, None)?;
+				}
+			}
+			_ => {} // ignored
+		}
+		return Ok(true);
+	}
+
+	match &mut app.search {
+		Some(search) if search.editing => {
+			let edit = edit_query(&mut search.query, key);
+			search.compile(app.regex_search);
+			match edit {
+				QueryEdit::Editing => incremental_search(app, term_size),
+				QueryEdit::Cancelled => {
+					(search.query, search.editing, search.regex) = (String::new(), false, None);
+					incremental_search(app, term_size); // back to the origin
+					app.search = None;
 				}
-				KeyEvent { code: Char(c), .. } => {
-					search.query.push(*c);
+				QueryEdit::Submitted if search.query.is_empty() => app.search = None,
+				QueryEdit::Submitted if search.invalid().is_some() => {
+					app.message = Some(format!("invalid regex: {}", search.invalid().unwrap()));
+					app.search = None; // incremental_search left the selection where it was
 				}
-				KeyEvent {
-					code: KeyCode::Backspace,
-					..
-				} => {
-					search.query.pop();
+				QueryEdit::Submitted => search.editing = false, // already on the first match
+			}
+			return Ok(true);
+		}
+		_ => {}
+	}
+	if let Some(filter) = app.author_filter.as_mut().filter(|filter| filter.editing) {
+		match edit_query(&mut filter.query, key) {
+			QueryEdit::Editing => {}
+			QueryEdit::Submitted if !filter.query.is_empty() => filter.editing = false,
+			QueryEdit::Cancelled | QueryEdit::Submitted => app.author_filter = None,
+		}
+		return Ok(true);
+	}
+	if let Some(prompt) = &mut app.rev_prompt {
+		match edit_query(&mut prompt.query, key) {
+			QueryEdit::Editing => {}
+			QueryEdit::Cancelled => app.rev_prompt = None,
+			QueryEdit::Submitted => {
+				let RevPrompt { query: rev, compare } = app.rev_prompt.take().unwrap();
+				if !rev.is_empty() {
+					let result = match compare {
+						true => app.compare_rev(&rev),
+						false => app.blame_rev(&rev),
+					};
+					if let Err(e) = result {
+						app.message = Some(format!("{}: {}", rev, e));
+					}
 				}
-				KeyEvent {
-					code: KeyCode::Enter, ..
-				} => {
-					search.editing = false;
-					handle_search(&app.blame, &search.query, &mut app.blame_state, true);
+			}
+		}
+		return Ok(true);
+	}
+
+	if let Some(pending) = app.pending_mark.take() {
+		if let (Char(letter), Some(index)) = (key.code, app.blame_state.selected()) {
+			let marks = app.marks.for_blame(app.commit_stack.last().unwrap());
+			match pending {
+				Action::SetMark if letter.is_ascii_alphabetic() => {
+					marks.insert(letter, index);
 				}
-				_ => {} // ignored
+				Action::JumpToMark if letter.is_ascii_alphabetic() || letter == '\'' => match marks.get(&letter) {
+					Some(&line) => {
+						marks.insert('\'', index);
+						app.blame_state
+							.select(Some(line.min(app.blame.len().saturating_sub(1))));
+					}
+					None => app.message = Some(format!("mark {} isn't set", letter)),
+				},
+				_ => {} // not a mark
 			}
+		}
+		return Ok(true);
+	}
+
+	let action = app.keymap.action(key);
+	if let Some(compare) = app.compare.as_mut().filter(|compare| compare.focused) {
+		if app.right_panel.is_none() && move_compare(compare, action, app.page_scroll.lines(term_size.height)) {
 			return Ok(true);
 		}
-	} else if let Some(line_number) = &mut app.line_number {
+	}
+	if let Some(line_number) = &mut app.line_number {
 		match key {
 			KeyEvent { code: KeyCode::Esc, .. }
 			| KeyEvent {
@@ -156,10 +1486,8 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Rect) -> Result<bool,
 			} => {
 				line_number.clear();
 			}
-			KeyEvent { code: Char(c), .. } => {
-				if '0' <= *c && *c <= '9' {
-					line_number.push(*c);
-				}
+			KeyEvent { code: Char(c), .. } if c.is_ascii_digit() => {
+				line_number.push(*c);
 			}
 			KeyEvent {
 				code: KeyCode::Backspace,
@@ -170,8 +1498,14 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Rect) -> Result<bool,
 			KeyEvent {
 				code: KeyCode::Enter, ..
 			} => {
-				if let Ok(index) = line_number.parse::<usize>() {
-					app.blame_state.select(Some(index.clamp(1, app.blame.len()) - 1));
+				if let Ok(line_num) = line_number.parse::<usize>() {
+					match app.line_index(line_num) {
+						Some(index) => app.blame_state.select(Some(index)),
+						None => {
+							let last = app.blame.last().map_or(0, |line| line.line_num);
+							app.message = Some(format!("line {} is past the end (last line is {})", line_num, last));
+						}
+					}
 					app.line_number = None;
 				}
 			}
@@ -180,106 +1514,455 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Rect) -> Result<bool,
 		return Ok(true);
 	}
 
-	match key {
+	match action {
 		// scroll
-		KeyEvent {
-			code: Char('j') | KeyCode::Down,
-			..
-		} => scroll(app, term_size, 1),
-		KeyEvent {
-			code: Char('k') | KeyCode::Up,
-			..
-		} => scroll(app, term_size, -1),
-		KeyEvent { code: Char('d'), .. }
-		| KeyEvent {
-			code: KeyCode::PageDown,
-			..
-		} => scroll(app, term_size, (term_size.height / 2).try_into().unwrap()),
-		KeyEvent { code: Char('u'), .. }
-		| KeyEvent {
-			code: KeyCode::PageUp, ..
-		} => scroll(app, term_size, -i16::try_from(term_size.height / 2).unwrap()),
-		KeyEvent { code: Char('g'), .. }
-		| KeyEvent {
-			code: KeyCode::Home, ..
-		} => match &app.right_panel {
+		Some(Action::ScrollDown) if wrap_selection(app, true) => {}
+		Some(Action::ScrollUp) if wrap_selection(app, false) => {}
+		Some(Action::ScrollDown) => scroll(app, term_size, 1),
+		Some(Action::ScrollUp) => scroll(app, term_size, -1),
+		Some(Action::PageDown) => scroll(app, term_size, app.page_scroll.lines(term_size.height)),
+		Some(Action::PageUp) => scroll(app, term_size, -app.page_scroll.lines(term_size.height)),
+		Some(Action::Top) => match &app.right_panel {
 			Some(_) => app.line_history_scroll = 0,
-			None => app.blame_state.select(Some(0)),
+			None => app.blame_state.select(match navigable_rows(app) {
+				Some(rows) => rows.first().map(Row::first),
+				None => (!app.blame.is_empty()).then_some(0),
+			}),
 		},
-		KeyEvent { code: Char('G'), .. } | KeyEvent { code: KeyCode::End, .. } => match &app.right_panel {
-			Some(line_history) => {
-				app.line_history_scroll = u16::try_from(line_history.height())
-					.unwrap()
-					.saturating_sub(term_size.height)
-			}
-			None => app.blame_state.select(Some(app.blame.len() - 1)),
+		Some(Action::Bottom) => match &app.right_panel {
+			Some(panel) => app.line_history_scroll = panel.max_scroll(term_size),
+			None => app.blame_state.select(match navigable_rows(app) {
+				Some(rows) => rows.last().map(Row::first),
+				None => app.blame.len().checked_sub(1),
+			}),
 		},
-		KeyEvent { code: Char(':'), .. } => {
+		Some(Action::ScrollRight) => {
+			let longest = app.blame.iter().map(|line| line.code_width()).max().unwrap_or(0);
+			app.line_format.code_scroll = (app.line_format.code_scroll + 4).min(longest);
+		}
+		Some(Action::ScrollLeft) => app.line_format.code_scroll = app.line_format.code_scroll.saturating_sub(4),
+		Some(Action::LineNumbers) => {
+			app.line_numbers = !app.line_numbers;
+			app.layout = None;
+		}
+		Some(Action::SplitLeft) => app.split = app.split.saturating_sub(SPLIT_STEP).max(SPLIT_RANGE.0),
+		Some(Action::SplitRight) => app.split = (app.split + SPLIT_STEP).min(SPLIT_RANGE.1),
+		Some(Action::Minimap) => app.minimap = !app.minimap,
+		Some(Action::AuthorBadges) => {
+			app.line_format.author_badges = !app.line_format.author_badges;
+			app.layout = None;
+		}
+		Some(Action::Layout) if !app.layouts.is_empty() => {
+			app.apply_layout(app.layout.map_or(0, |i| (i + 1) % app.layouts.len()));
+		}
+		Some(Action::Summary) => {
+			app.layout = None;
+			app.line_format.summary_width = match app.line_format.summary_width {
+				0 => SUMMARY_WIDTHS[1],
+				_ => 0,
+			}
+		}
+		Some(Action::SummaryWidth) => {
+			let next = SUMMARY_WIDTHS
+				.iter()
+				.position(|&width| width == app.line_format.summary_width);
+			app.line_format.summary_width = SUMMARY_WIDTHS[next.map_or(0, |i| (i + 1) % SUMMARY_WIDTHS.len())];
+		}
+		Some(action @ (Action::SetMark | Action::JumpToMark)) => app.pending_mark = Some(action),
+		Some(action @ (Action::NextCommit | Action::PrevCommit)) => {
+			let selected = app.blame_state.selected().unwrap_or(0);
+			if let Some(start) = commit_boundary(&app.blame, selected, action == Action::NextCommit) {
+				app.blame_state.select(Some(start));
+			}
+		}
+		Some(action @ (Action::NextAuthor | Action::PrevAuthor)) => {
+			let selected = app.blame_state.selected().unwrap_or(0);
+			if let Some(start) = author_boundary(&app.blame, selected, action == Action::NextAuthor) {
+				app.blame_state.select(Some(start));
+			}
+		}
+		Some(Action::ViewDown) => scroll_view(app, term_size, 1),
+		Some(Action::ViewUp) => scroll_view(app, term_size, -1),
+		Some(Action::Center) => app.center = !app.center,
+		Some(Action::Wrap) => app.wrap = !app.wrap,
+		Some(Action::Dates) => app.line_format.absolute_dates = !app.line_format.absolute_dates,
+		Some(Action::Fold) => {
+			app.folded = !app.folded;
+			app.expanded_folds.clear();
+		}
+		Some(Action::GotoLine) => {
 			app.line_number = Some(String::new());
 		}
-		// search
-		KeyEvent { code: Char('/'), .. } => {
-			app.search = Some(Search {
+		// search
+		Some(Action::Search) => {
+			app.search = Some(Search {
+				editing: true,
+				query: String::new(),
+				origin: (app.blame_state.selected(), app.line_history_scroll),
+				..Default::default()
+			});
+			if let Some(panel) = &mut app.right_panel {
+				panel.search_match = None;
+			}
+		}
+		Some(Action::SearchNext) => search_next(app, term_size, true),
+		Some(Action::SearchPrev) => search_next(app, term_size, false),
+		// other interactions
+		Some(Action::ShowCommit) => {
+			let rows = if app.folded {
+				fold_rows(&app.blame, &app.expanded_folds)
+			} else {
+				vec![]
+			};
+			if let Some(&Row::Fold { start, .. }) = row_of(&rows, app.blame_state.selected()).map(|row| &rows[row]) {
+				app.expanded_folds.insert(start);
+			} else if let Some(index) = app.blame_state.selected() {
+				app.right_panel = Some(RightPanel::new(
+					PanelKind::Show,
+					render::show(app.repo, app.blame[index].commit, &app.theme.diff),
+				));
+			}
+		}
+		Some(Action::LineHistory) => {
+			let selected = app.blame_state.selected().map(|index| (index, index));
+			if let Some((first, last)) = app.selected_range().or(selected) {
+				let commit_path = app.commit_stack.last().unwrap();
+				app.follow_lines(FollowedLines {
+					path: commit_path.path.to_owned(),
+					start: app.blame[first].line_num as usize - 1,
+					end: app.blame[last].line_num as usize - 1,
+					commit: commit_path.commit,
+					pinned: app.pinned_line(first, last),
+				})?;
+				app.range_start = None;
+			}
+		}
+		Some(Action::FirstParent) => {
+			app.log_options.first_parent = !app.log_options.first_parent;
+			app.message = Some(match app.log_options.first_parent {
+				true => "line history follows first parents only".to_owned(),
+				false => "line history follows all parents".to_owned(),
+			});
+			let history_open = matches!(&app.right_panel, Some(panel) if panel.kind == PanelKind::LineHistory);
+			if let Some(lines) = app.followed.clone().filter(|_| history_open) {
+				app.follow_lines(lines)?;
+			}
+		}
+		Some(Action::SelectRange) => {
+			app.range_start = match app.range_start {
+				Some(_) => None,
+				None => app.blame_state.selected(),
+			};
+		}
+		Some(Action::FileDiff) => {
+			if let Some(index) = app.blame_state.selected() {
+				let line = &app.blame[index];
+				let path = line.path.as_ref().unwrap_or(&app.commit_stack.last().unwrap().path);
+				app.right_panel = Some(RightPanel::new(
+					PanelKind::FileDiff,
+					render::show_file(app.repo, line.commit, path, app.line_format.abbrev, &app.theme.diff),
+				));
+				app.line_history_scroll = 0;
+			}
+		}
+		Some(Action::CommitDetails) => {
+			if matches!(&app.right_panel, Some(panel) if panel.kind == PanelKind::CommitDetails) {
+				app.right_panel = None;
+				app.line_history_scroll = 0;
+			} else if let Some(index) = app.blame_state.selected() {
+				let commit = app.blame[index].commit;
+				let mut text = match &app.commit_details {
+					Some((cached, text)) if *cached == commit => text.clone(),
+					_ => {
+						let text = render::commit_details(app.repo, commit);
+						app.commit_details = Some((commit, text.clone()));
+						text
+					}
+				};
+				// a rename, or a copy when detecting copies
+				let commit_path = app.commit_stack.last().unwrap();
+				if let Some(path) = app.blame[index].path.as_ref().filter(|path| **path != commit_path.path) {
+					text.lines
+						.insert(1, Line::from(format!("line from: {}", path.display())));
+				}
+				app.right_panel = Some(RightPanel::new(PanelKind::CommitDetails, text));
+				app.line_history_scroll = 0;
+			}
+		}
+		Some(Action::BlameParent) => {
+			if let Some(index) = app.blame_state.selected() {
+				// an uncommitted line's "parent" is HEAD
+				if app.blame[index].commit.is_zero() {
+					let head = app.repo.head()?.peel_to_commit()?.id();
+					app.blame_parent(index, head)?;
+					return Ok(true);
+				}
+				let commit = app.repo.find_commit(app.blame[index].commit)?;
+				match commit.parent_count() {
+					0 => {
+						app.message = Some(format!(
+							"reached initial commit {:.*}",
+							app.line_format.abbrev,
+							commit.id()
+						))
+					}
+					1 => app.blame_parent(index, commit.parent_id(0)?)?,
+					_ => {
+						let mut lines = vec![
+							Line::from(format!(
+								"blame at which parent of {:.*}?",
+								app.line_format.abbrev,
+								commit.id()
+							)),
+							Line::default(),
+						];
+						for (i, parent) in commit.parents().enumerate() {
+							lines.push(Line::from(vec![
+								Span::raw(format!("{}  ", i + 1)),
+								Span::styled(
+									format!("{:.*}", app.line_format.abbrev, parent.id()),
+									Style::default().fg(Color::Yellow),
+								),
+								Span::raw(format!(" {}", parent.summary().unwrap_or_default())),
+							]));
+						}
+						app.popup = Some(lines.into());
+						app.parent_prompt = Some(ParentPrompt {
+							index,
+							parents: commit.parent_ids().collect(),
+						});
+					}
+				}
+			}
+		}
+		Some(Action::BlameRev) => {
+			app.rev_prompt = Some(RevPrompt {
+				query: String::new(),
+				compare: false,
+			})
+		}
+		Some(Action::Compare) if app.compare.is_some() => app.compare = None,
+		Some(Action::Compare) => {
+			app.rev_prompt = Some(RevPrompt {
+				query: String::new(),
+				compare: true,
+			})
+		}
+		Some(Action::SwitchPane) => {
+			if let Some(compare) = &mut app.compare {
+				compare.focused = !compare.focused;
+			}
+		}
+		Some(Action::PopBlame) if app.commit_stack.len() > 1 => app.pop_blame_to(app.commit_stack.len() - 1)?,
+		Some(Action::Reload) => app.message = Some(app.reload()?),
+		Some(Action::BlameStack) => {
+			let entries = app.stack_entries();
+			app.stack_picker = Some(StackPicker {
+				state: ListState::default().with_selected(Some(entries.len() - 1)),
+				entries,
+			});
+		}
+		Some(Action::FileLog) => {
+			let top = app.commit_stack.last().unwrap();
+			let log = git::FileLog::spawn(app.repo, &top.path, top.commit, app.log_options.first_parent)?;
+			app.log_picker = Some(LogPicker {
+				loading: Some(log),
+				started: Instant::now(),
+				commits: vec![],
+				entries: vec![],
+				state: ListState::default(),
+			});
+		}
+		Some(Action::OpenFile) => {
+			let commit = app.commit_stack.last().unwrap().commit;
+			app.file_picker = Some(FilePicker {
+				files: git::tree_files(app.repo, commit)?,
+				query: String::new(),
+				state: ListState::default().with_selected(Some(0)),
+			});
+		}
+		Some(Action::AuthorFilter) => {
+			app.author_filter = Some(Search {
 				editing: true,
 				query: String::new(),
-			});
+				..Default::default()
+			})
+		}
+		Some(Action::Quickfix) => {
+			// the filtered author, or else whoever wrote the selected line
+			let lines: Vec<&git::BlameHunk> = match (app.active_author_filter(), app.blame_state.selected()) {
+				(Some(filter), _) => app.blame.iter().filter(|line| filter.matches_author(line)).collect(),
+				(None, Some(index)) => {
+					let email = &app.blame[index].author_email;
+					app.blame.iter().filter(|line| &line.author_email == email).collect()
+				}
+				(None, None) => vec![],
+			};
+			let rel_path = &app.commit_stack.last().unwrap().path;
+			let path = match app.repo.workdir() {
+				Some(workdir) => workdir.join(rel_path),
+				None => rel_path.to_owned(),
+			};
+			// not in the shared temp dir, where another user could plant a symlink or overwrite it
+			let dir = prefs::state_dir().ok_or("no HOME to write the quickfix file under")?;
+			fs::create_dir_all(&dir)?;
+			let out_path = dir.join("quickfix");
+			let mut out = io::BufWriter::new(fs::File::create(&out_path)?);
+			print::print_blame(&lines, print::Format::Quickfix, app.line_format.abbrev, &path, &mut out)?;
+			out.flush()?;
+			app.message = Some(format!(
+				"wrote {} lines to {}, open them with vim -q",
+				lines.len(),
+				out_path.display()
+			));
+		}
+		Some(Action::Heatmap) => app.heatmap = !app.heatmap,
+		Some(Action::Dim) => {
+			app.dim = !app.dim;
+			if app.dim {
+				app.message = Some(format!(
+					"dimming lines {} than {} days",
+					if app.dim_config.newer { "newer" } else { "older" },
+					app.dim_config.days
+				));
+			}
 		}
-		KeyEvent { code: Char('n'), .. } => {
-			if let Some(search) = &app.search {
-				handle_search(&app.blame, &search.query, &mut app.blame_state, true);
+		Some(Action::AuthorStats) => {
+			if matches!(&app.right_panel, Some(panel) if panel.kind == PanelKind::AuthorStats) {
+				app.right_panel = None;
+			} else {
+				app.right_panel = Some(RightPanel::new(PanelKind::AuthorStats, author_stats_text(&app.blame)));
 			}
+			app.line_history_scroll = 0;
 		}
-		KeyEvent { code: Char('N'), .. } => {
-			if let Some(search) = &app.search {
-				handle_search(&app.blame, &search.query, &mut app.blame_state, false);
+		Some(Action::IgnoreWhitespace) => {
+			app.blame_options.ignore_whitespace = !app.blame_options.ignore_whitespace;
+			if let Err(e) = app.reblame() {
+				app.blame_options.ignore_whitespace = !app.blame_options.ignore_whitespace;
+				return Err(e);
 			}
 		}
-		// other interactions
-		KeyEvent {
-			code: KeyCode::Enter, ..
-		} => {
+		Some(Action::DetectCopies) => {
+			app.blame_options.detect_copies = !app.blame_options.detect_copies;
+			if let Err(e) = app.reblame() {
+				app.blame_options.detect_copies = !app.blame_options.detect_copies;
+				return Err(e);
+			}
+		}
+		Some(Action::CopyCommit) => {
 			if let Some(index) = app.blame_state.selected() {
-				app.right_panel = Some(git::show(app.repo, app.blame[index].commit));
+				let commit = app.blame[index].commit.to_string();
+				app.message = Some(match copy_to_clipboard(&mut app.clipboard, &commit) {
+					Ok(()) => format!("copied {}", commit),
+					Err(e) => {
+						app.errors
+							.push(SystemTime::now(), format!("couldn't copy {}: {}", commit, e));
+						format!("{} (not copied: {})", commit, e)
+					}
+				});
 			}
 		}
-		KeyEvent { code: Char('w'), .. } => {
+		Some(Action::CopyBlame) => {
+			let text = app.visible_text();
+			let lines = text.lines().count();
+			app.message = Some(match copy_to_clipboard(&mut app.clipboard, &text) {
+				Ok(()) => format!("copied {} lines", lines),
+				Err(e) => {
+					app.errors
+						.push(SystemTime::now(), format!("couldn't copy the blame: {}", e));
+					format!("not copied: {}", e)
+				}
+			});
+		}
+		Some(Action::CopyPermalink) => {
 			if let Some(index) = app.blame_state.selected() {
+				// link to the version being viewed so the line number points at the selected line
 				let commit_path = app.commit_stack.last().unwrap();
-				app.right_panel = Some(git::log_follow(app.repo, &commit_path.path, index, commit_path.commit));
+				let url = match commit_path.commit.is_zero() {
+					true => Err("the working tree isn't on the web".into()),
+					false => git::blob_web_url(
+						app.repo,
+						commit_path.commit,
+						&commit_path.path,
+						app.blame[index].line_num,
+					),
+				};
+				let message = match url {
+					Ok(url) => match copy_to_clipboard(&mut app.clipboard, &url) {
+						Ok(()) => Ok(format!("copied {}", url)),
+						Err(e) => Err(format!("{} (not copied: {})", url, e)),
+					},
+					Err(e) => Err(format!("no permalink: {}", e)),
+				};
+				app.message = Some(message.unwrap_or_else(|e| {
+					app.errors.push(SystemTime::now(), e.clone());
+					e
+				}));
 			}
 		}
-		KeyEvent { code: Char('b'), .. } => {
+		Some(Action::OpenCommit) => {
 			if let Some(index) = app.blame_state.selected() {
-				let blame = &app.blame[index];
-				let parent = app.repo.find_commit(blame.commit)?.parent_id(0)?;
-				let line_path = match blame.path.to_owned() {
-					Some(p) => p,
-					None => app.commit_stack.last().unwrap().path.to_owned(),
+				let url = match app.blame[index].commit.is_zero() {
+					true => Err("not committed yet".into()),
+					false => git::commit_web_url(app.repo, app.blame[index].commit),
 				};
-				app.blame = git::blame(app.repo, &line_path, parent)?;
-				app.blame_state.select(Some(index.min(app.blame.len() - 1)));
-				app.commit_stack.push(CommitPath {
-					commit: parent,
-					path: line_path,
-				});
+				let message = match url {
+					Ok(url) => match open::that_detached(&url) {
+						Ok(()) => Ok(format!("opened {}", url)),
+						Err(e) => Err(format!("couldn't open {}: {}", url, e)),
+					},
+					Err(e) => Err(e.to_string()),
+				};
+				app.message = Some(message.unwrap_or_else(|e| {
+					app.errors.push(SystemTime::now(), e.clone());
+					e
+				}));
 			}
 		}
-		KeyEvent { code: Char('B'), .. } => {
-			if app.commit_stack.len() > 1 {
-				app.commit_stack.pop();
-				let commit_path = app.commit_stack.last().unwrap();
-				app.blame = git::blame(app.repo, &commit_path.path, commit_path.commit)?;
-				if let Some(index) = app.blame_state.selected() {
-					app.blame_state.select(Some(index.min(app.blame.len() - 1)));
+		Some(Action::Edit) => {
+			if let Some(index) = app.blame_state.selected() {
+				// the file under its current name, since that's what's on disk
+				let workdir = app
+					.repo
+					.workdir()
+					.ok_or("a bare repository has no working tree to edit")?;
+				let path = workdir.join(&app.commit_stack[0].path);
+				if !path.exists() {
+					return Err(format!("{} doesn't exist in the working tree", path.display()).into());
+				}
+				// the blamed commit's line numbers, moved past whatever changed since
+				let top = app.commit_stack.last().unwrap();
+				let (line, unchanged) = git::worktree_line(
+					app.repo,
+					top.commit,
+					&top.path,
+					&app.commit_stack[0].path,
+					app.blame[index].line_num as usize,
+				)?;
+				if !unchanged {
+					app.message = Some("the line has changed in the working tree; opening near it".to_owned());
 				}
+				app.pending_edit = Some((path, line));
 			}
 		}
-		KeyEvent { code: Char('h'), .. } => app.popup = Some(make_help_text()),
-		KeyEvent {
-			code: Char('q') | KeyCode::Esc,
-			..
-		} => {
-			if app.right_panel.is_some() {
+		Some(Action::Help) => app.popup = Some(app.keymap.help_text()),
+		Some(Action::ErrorLog) => app.popup = Some(app.errors.text()),
+		Some(Action::Quit) => {
+			if app.range_start.is_some() {
+				app.range_start = None;
+			} else if app.history.take().is_some() {
+				// keep what's already loaded
+				if app
+					.right_panel
+					.as_ref()
+					.is_some_and(|panel| panel.text.lines.is_empty())
+				{
+					app.right_panel = None;
+				}
+				app.message = Some("cancelled line history".to_owned());
+			} else if app.right_panel.is_none() && app.compare.is_some() {
+				app.compare = None;
+			} else if app.right_panel.is_some() {
 				app.right_panel = None;
 				app.line_history_scroll = 0;
 			} else {
@@ -291,85 +1974,296 @@ fn handle_input(key: &KeyEvent, app: &mut App, term_size: &Rect) -> Result<bool,
 	Ok(true)
 }
 
+fn author_stats_text(blame: &[git::BlameHunk]) -> Text<'static> {
+	let mut lines = vec![Line::from(Span::styled(
+		format!("{} lines", blame.len()),
+		Style::default().add_modifier(Modifier::BOLD),
+	))];
+	for author in stats::by_author(blame) {
+		let percent = author.lines as f64 * 100.0 / blame.len() as f64;
+		lines.push(Line::from(vec![
+			Span::styled(format!("{:6} ", author.lines), Style::default().fg(Color::Yellow)),
+			Span::styled(format!("{:5.1}% ", percent), Style::default().fg(Color::LightRed)),
+			Span::raw(format!("{} <{}>", author.author, author.email)),
+		]));
+	}
+	lines.into()
+}
+
+fn copy_to_clipboard(clipboard: &mut Option<arboard::Clipboard>, text: &str) -> Result<(), arboard::Error> {
+	if clipboard.is_none() {
+		*clipboard = Some(arboard::Clipboard::new()?);
+	}
+	clipboard.as_mut().unwrap().set_text(text)
+}
+
+// a taller terminal can leave the right panel scrolled past the end of its text
+fn clamp_scroll(app: &mut App, term_size: &Rect) {
+	if let Some(panel) = &app.right_panel {
+		app.line_history_scroll = app.line_history_scroll.min(panel.max_scroll(term_size));
+	}
+}
+
 fn scroll(app: &mut App, term_size: &Rect, amount: i16) {
 	match &app.right_panel {
-		Some(line_history) => {
-			let max = u16::try_from(line_history.height())
-				.unwrap()
-				.saturating_sub(term_size.height);
+		Some(panel) => {
+			let max = panel.max_scroll(term_size);
 			app.line_history_scroll = app.line_history_scroll.saturating_add_signed(amount).clamp(0, max);
 		}
-		None => {
-			match app.blame_state.selected() {
-				Some(index) => {
-					let new_index = index.saturating_add_signed(amount.into());
-					app.blame_state.select(Some(new_index.clamp(0, app.blame.len() - 1)));
-				}
-				None => {
-					app.blame_state.select(Some(0));
+		// step by rows so a fold is one step and filtered out lines are skipped
+		None => match navigable_rows(app) {
+			Some(rows) if !rows.is_empty() => {
+				let selected = app.blame_state.selected();
+				let row = match (row_of(&rows, selected), selected) {
+					(Some(row), _) => Some(row.saturating_add_signed(amount.into()).min(rows.len() - 1)),
+					// the selection is on a line the filter skips
+					(None, Some(index)) if amount > 0 => rows.iter().position(|row| row.first() > index),
+					(None, Some(index)) => rows.iter().rposition(|row| row.first() < index),
+					(None, None) => Some(0),
+				};
+				if let Some(row) = row {
+					app.blame_state.select(Some(rows[row].first()));
 				}
+			}
+			Some(_) => {} // nothing matches the filter
+			None => move_selection(&mut app.blame_state, app.blame.len(), amount),
+		},
+	}
+}
+
+// with wrap_scroll, moves the selection from the last row to the first going down or the first to the last going up.
+// returns whether it did, leaving anywhere else to scroll
+fn wrap_selection(app: &mut App, down: bool) -> bool {
+	if !app.wrap_scroll || app.right_panel.is_some() || app.blame.is_empty() {
+		return false;
+	}
+	let rows = navigable_rows(app).unwrap_or_else(|| (0..app.blame.len()).map(Row::Line).collect());
+	let (Some(first), Some(last)) = (rows.first(), rows.last()) else {
+		return false; // nothing matches the filter
+	};
+	let (from, to) = if down { (last, first) } else { (first, last) };
+	match app.blame_state.selected() {
+		Some(index) if from.contains(index) => {
+			app.blame_state.select(Some(to.first()));
+			true
+		}
+		_ => false,
+	}
+}
+
+// returns whether action moved the compared blame's selection
+fn move_compare(compare: &mut Compare, action: Option<Action>, page: i16) -> bool {
+	match action {
+		Some(Action::ScrollDown) => move_selection(&mut compare.state, compare.blame.len(), 1),
+		Some(Action::ScrollUp) => move_selection(&mut compare.state, compare.blame.len(), -1),
+		Some(Action::PageDown) => move_selection(&mut compare.state, compare.blame.len(), page),
+		Some(Action::PageUp) => move_selection(&mut compare.state, compare.blame.len(), -page),
+		Some(Action::Top) => compare.state.select((!compare.blame.is_empty()).then_some(0)),
+		Some(Action::Bottom) => compare.state.select(compare.blame.len().checked_sub(1)),
+		_ => return false,
+	}
+	true
+}
+
+// moves the list's viewport like vim's ctrl-e and ctrl-y, only moving the selection to keep it on screen
+fn scroll_view(app: &mut App, term_size: &Rect, amount: i16) {
+	if app.right_panel.is_some() {
+		return scroll(app, term_size, amount); // already scrolls by rows
+	}
+	let height = usize::from(app.blame_area.height.saturating_sub(1)); // minus the title
+	let state = match app.folded {
+		true => &mut app.fold_state,
+		false => &mut app.blame_state,
+	};
+	let offset = state
+		.offset()
+		.saturating_add_signed(amount.into())
+		.min(max_offset(&app.item_heights, height));
+	*state.offset_mut() = offset;
+	let Some(selected) = state.selected() else {
+		return;
+	};
+	let row = selected.clamp(offset, last_visible(&app.item_heights, offset, height));
+	if row != selected {
+		if app.folded {
+			let rows = fold_rows(&app.blame, &app.expanded_folds);
+			app.blame_state.select(Some(rows[row].first()));
+		} else {
+			app.blame_state.select(Some(row));
+		}
+	}
+}
+
+// one cell per row of height, colored by the author of the lines it covers, marking where the commit changes and
+// which lines (first and last, inclusive) are on screen
+fn minimap(blame: &[git::BlameHunk], height: usize, visible: (usize, usize)) -> Vec<Span<'static>> {
+	let lines = |row: usize| {
+		let first = row * blame.len() / height;
+		first..((row + 1) * blame.len() / height).max(first + 1)
+	};
+	(0..height)
+		.map(|row| {
+			let covered = lines(row);
+			if covered.start >= blame.len() {
+				return Span::raw(" ");
+			}
+			// several rows can cover the same line when the file is shorter than the bar
+			let new_lines = row == 0 || lines(row - 1).start != covered.start;
+			let boundary = new_lines
+				&& covered
+					.clone()
+					.any(|i| i > 0 && i < blame.len() && blame[i].commit != blame[i - 1].commit);
+			let on_screen = covered.start <= visible.1 && covered.end > visible.0;
+			let symbol = match (on_screen, boundary) {
+				(true, true) => "╂",
+				(true, false) => "┃",
+				(false, true) => "─",
+				(false, false) => " ",
 			};
+			Span::styled(
+				symbol,
+				Style::default()
+					.fg(Color::White)
+					.bg(render::author_color(&blame[covered.start].author_email)),
+			)
+		})
+		.collect()
+}
+
+// the last row entirely on screen when the list starts at offset
+fn last_visible(heights: &[usize], offset: usize, height: usize) -> usize {
+	let mut used = 0;
+	for (row, &row_height) in heights.iter().enumerate().skip(offset) {
+		used += row_height;
+		if used > height {
+			return row.saturating_sub(1).max(offset);
 		}
 	}
+	heights.len().saturating_sub(1)
 }
 
-fn handle_search(blame: &[git::BlameHunk<'_>], query: &str, blame_state: &mut ListState, forward: bool) {
-	let range: Box<dyn Iterator<Item = usize>> = if forward {
-		let start = match blame_state.selected() {
-			Some(index) => index + 1,
-			None => 0,
-		};
-		Box::new(start..blame.len())
+// what j and k step through when folded or filtered by author, or None when that's every line
+fn navigable_rows(app: &App) -> Option<Vec<Row>> {
+	let filter = app.active_author_filter();
+	if !app.folded && filter.is_none() {
+		return None;
+	}
+	let rows = if app.folded {
+		fold_rows(&app.blame, &app.expanded_folds)
 	} else {
-		let end = blame_state.selected().unwrap_or(0);
-		Box::new((0..end).rev())
+		(0..app.blame.len()).map(Row::Line).collect()
 	};
-	for i in range {
-		let line = &blame[i].line.spans.last().unwrap().content;
-		if line.contains(query) {
-			blame_state.select(Some(i));
-			return;
+	Some(
+		rows.into_iter()
+			.filter(|row| filter.is_none_or(|filter| filter.matches_author(&app.blame[row.first()])))
+			.collect(),
+	)
+}
+
+fn clamp_selection(index: Option<usize>, len: usize) -> Option<usize> {
+	let last = len.checked_sub(1);
+	index.zip(last).map(|(index, last)| index.min(last))
+}
+
+fn move_selection(blame_state: &mut ListState, len: usize, amount: i16) {
+	if len == 0 {
+		return;
+	}
+	match blame_state.selected() {
+		Some(index) => {
+			let new_index = index.saturating_add_signed(amount.into());
+			blame_state.select(Some(new_index.min(len - 1)));
+		}
+		None => {
+			blame_state.select(Some(0));
+		}
+	};
+}
+
+// searches the right panel if it's open, otherwise the blame
+fn search_next(app: &mut App, term_size: &Rect, forward: bool) {
+	let Some(search) = &app.search else {
+		return;
+	};
+	match &mut app.right_panel {
+		Some(panel) => {
+			let len = panel.text.lines.len();
+			let start = panel.search_match.unwrap_or(match forward {
+				// so the line at the top of the panel is checked first
+				true => (usize::from(app.line_history_scroll) + len).saturating_sub(1) % len.max(1),
+				false => usize::from(app.line_history_scroll),
+			});
+			if let Some(i) = find_wrapping(len, start, forward, |i| search.matches_line(&panel.text.lines[i])) {
+				panel.search_match = Some(i);
+				app.line_history_scroll = u16::try_from(i).unwrap_or(u16::MAX).min(panel.max_scroll(term_size));
+			}
 		}
+		None => handle_search(&app.blame, search, &mut app.blame_state, forward),
 	}
 }
 
-fn make_help_text() -> Text<'static> {
-	let mut help = vec![
-		"h           this help",
-		"q  esc      close window",
-		"",
-		"    moving",
-		"",
-		"j  ↓        down one line",
-		"k  ↑        up one line",
-		"d  pgdown   down half a window",
-		"u  pgup     up half a window",
-		"G  end      to last line",
-		"g  home     to first line",
-		":123        to line 123",
-		"",
-		"    search",
-		"",
-		"/           start searching",
-		"enter       search forward",
-		"n           repeat search forward",
-		"N           repeat search backward",
-		"",
-		"    git",
-		"",
-		"enter       show commit",
-		"w           trace line through history (git -L)",
-		"b           reblame line at parent commit",
-		"B           undo/pop blame stack",
-	];
-	(help.drain(..).map(Line::from).collect::<Vec<_>>()).into()
+// moves to the first match from where the search started, including that line, as the query is typed
+fn incremental_search(app: &mut App, term_size: &Rect) {
+	let Some(search) = &app.search else {
+		return;
+	};
+	let (selection, scroll) = search.origin;
+	app.blame_state.select(selection);
+	app.line_history_scroll = scroll;
+	if let Some(panel) = &mut app.right_panel {
+		panel.search_match = None;
+	}
+	if search.query.is_empty() {
+		return;
+	}
+	match &app.right_panel {
+		// which checks from the top line of the panel
+		Some(_) => search_next(app, term_size, true),
+		None => {
+			let len = app.blame.len();
+			if len == 0 {
+				return;
+			}
+			let start = selection.map_or(len - 1, |i| (i + len - 1) % len);
+			if let Some(i) = find_wrapping(len, start, true, |i| search.matches(&app.blame[i])) {
+				app.blame_state.select(Some(i));
+			}
+		}
+	}
+}
+
+fn handle_search(blame: &[git::BlameHunk], search: &Search, blame_state: &mut ListState, forward: bool) {
+	let start = match blame_state.selected() {
+		Some(index) => index,
+		None if forward => blame.len().saturating_sub(1),
+		None => 0,
+	};
+	if let Some(i) = find_wrapping(blame.len(), start, forward, |i| search.matches(&blame[i])) {
+		blame_state.select(Some(i));
+	}
+}
+
+// the first index after start that matches, wrapping around at either end
+fn find_wrapping(len: usize, start: usize, forward: bool, mut matches: impl FnMut(usize) -> bool) -> Option<usize> {
+	(1..=len)
+		.map(|offset| {
+			if forward {
+				(start + offset) % len
+			} else {
+				(start + len - offset) % len
+			}
+		})
+		.find(|&i| matches(i))
 }
 
 fn ui(frame: &mut Frame, app: &mut App) {
-	let constraints = if app.right_panel.is_none() {
-		[Constraint::Percentage(100)].as_ref()
+	let constraints = if app.right_panel.is_none() && app.compare.is_none() {
+		vec![Constraint::Percentage(100)]
 	} else {
-		[Constraint::Percentage(50), Constraint::Percentage(50)].as_ref()
+		vec![
+			Constraint::Percentage(app.split),
+			Constraint::Percentage(100 - app.split),
+		]
 	};
 	let size = Rect::new(
 		frame.size().x,
@@ -381,46 +2275,398 @@ fn ui(frame: &mut Frame, app: &mut App) {
 		.direction(Direction::Horizontal)
 		.constraints(constraints)
 		.split(size);
+	app.blame_area = chunks[0];
+	// j and k move the right panel or the focused comparison instead
+	let main_focused = app.right_panel.is_none() && !app.compare.as_ref().is_some_and(|compare| compare.focused);
+	app.minimap_area = Rect::default();
+	if app.minimap && !app.blame.is_empty() && chunks[0].width > 1 {
+		app.blame_area.width -= 1;
+		app.minimap_area = Rect::new(chunks[0].right() - 1, chunks[0].y, 1, chunks[0].height);
+	}
 
-	let items: Vec<ListItem> = app.blame.iter().map(|line| ListItem::new(line.line.clone())).collect();
+	// the lines git hasn't gotten to yet would look like the oldest
+	let age_range = match (
+		app.blame.iter().map(|line| line.commit_time).min(),
+		app.blame.iter().map(|line| line.commit_time).max(),
+	) {
+		(Some(oldest), Some(newest)) if app.heatmap && app.loading.is_none() => Some((oldest, newest)),
+		_ => None,
+	};
+	let line_format = app.display_format();
+	let author_filter = app.active_author_filter();
+	let now = SystemTime::now();
+	let range = app
+		.selected_range()
+		.map(|(first, last)| app.blame[first].line_num..=app.blame[last].line_num);
+	let width = usize::from(chunks[0].width);
+	let line_text = |line: &git::BlameHunk| -> Text<'static> {
+		match app.wrap {
+			true => line.to_wrapped_text(&line_format, width),
+			false => line.to_line(&line_format).into(),
+		}
+	};
+	let item = |line: &git::BlameHunk, mut text: Text<'static>| {
+		if author_filter.is_some_and(|filter| !filter.matches_author(line)) || app.dims(line, now) {
+			for span in text.lines.iter_mut().flat_map(|line| &mut line.spans) {
+				span.style = span.style.fg(app.theme.gutter);
+			}
+		}
+		let mut item = ListItem::new(text);
+		if range.as_ref().is_some_and(|range| range.contains(&line.line_num)) {
+			return item.style(Style::default().bg(app.theme.selection));
+		}
+		if let Some((oldest, newest)) = age_range {
+			item = item.style(Style::default().bg(heatmap_color(&app.theme.age, line.commit_time, oldest, newest)));
+		}
+		match &app.search {
+			Some(search) if search.matches(line) => item.style(Style::default().bg(app.theme.search_match)),
+			_ => item,
+		}
+	};
+	let rows = if app.folded {
+		fold_rows(&app.blame, &app.expanded_folds)
+	} else {
+		vec![]
+	};
+	let items: Vec<ListItem> = if app.folded {
+		rows.iter()
+			.map(|row| match *row {
+				Row::Line(index) => item(&app.blame[index], line_text(&app.blame[index])),
+				Row::Fold { start, len } => item(
+					&app.blame[start],
+					app.blame[start].to_fold_line(&line_format, len).into(),
+				),
+			})
+			.collect()
+	} else {
+		app.blame.iter().map(|line| item(line, line_text(line))).collect()
+	};
+	app.item_heights = items.iter().map(|item| item.height()).collect();
 	let commit_path = app.commit_stack.last().unwrap();
-	let title = Line::from(vec![
+	let mut title_spans = vec![
 		Span::styled(
-			commit_path.commit.to_string(),
-			Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+			match commit_path.commit.is_zero() {
+				true => "working tree".to_owned(),
+				false => format!("{:.*}", app.line_format.abbrev, commit_path.commit),
+			},
+			Style::default().fg(app.theme.title).add_modifier(Modifier::BOLD),
 		),
 		Span::raw(" "),
 		Span::styled(
 			commit_path.path.to_str().unwrap(),
 			Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD),
 		),
-	]);
-	let list = List::new(items)
-		.block(Block::default().title(title))
-		.highlight_style(Style::default().bg(Color::Indexed(237))); // 232 is black, 255 is white; 237 is dark gray
-	frame.render_stateful_widget(list, chunks[0], &mut app.blame_state);
-
-	if let Some(log) = &app.right_panel {
-		let paragraph = Paragraph::new(log.clone())
+	];
+	if let Some(date) = git::commit_date(app.repo, commit_path.commit, app.line_format.absolute_dates) {
+		title_spans.push(Span::styled(format!(" {}", date), Style::default().fg(Color::Gray)));
+	}
+	// how many times `b` has been pressed since, which `S` lists
+	if app.commit_stack.len() > 1 {
+		title_spans.push(Span::styled(
+			format!(" [{} deep]", app.commit_stack.len() - 1),
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	// git blame follows renames, so `b` can land on an older name
+	let current_path = &app.commit_stack[0].path;
+	if commit_path.path != *current_path {
+		title_spans.push(Span::styled(
+			format!(" (now {})", current_path.display()),
+			Style::default().fg(Color::LightBlue),
+		));
+	}
+	let selected_path = app.blame_state.selected().and_then(|i| app.blame.get(i)?.path.as_ref());
+	if let Some(path) = selected_path.filter(|path| **path != commit_path.path) {
+		title_spans.push(Span::styled(
+			format!(" [line from {}]", path.display()),
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	if let Some(filter) = app.author_filter.as_ref().filter(|filter| !filter.editing) {
+		title_spans.push(Span::styled(
+			format!(" [author: {}]", filter.query),
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	if let Some((first, last)) = app.selected_range() {
+		title_spans.push(Span::styled(
+			format!(
+				" [selecting lines {}-{}, w for history]",
+				app.blame[first].line_num, app.blame[last].line_num
+			),
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	if app.blame_options.ignore_whitespace {
+		title_spans.push(Span::styled(
+			" [whitespace ignored]",
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	if app.blame_options.detect_copies {
+		title_spans.push(Span::styled(" [copies detected]", Style::default().fg(Color::Magenta)));
+	}
+	if let Some(encoding) = app.blame.iter().find_map(|line| line.encoding) {
+		title_spans.push(Span::styled(
+			format!(" [decoded from {}]", encoding),
+			Style::default().fg(Color::Magenta),
+		));
+	}
+	let mut title = Line::from(title_spans);
+	let loading_status = app.loading.as_ref().map(|loading| {
+		let elapsed = loading.started.elapsed();
+		let frame_index = (elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize % SPINNER.len();
+		let status = format!(
+			"{} blaming {}… {}s",
+			SPINNER[frame_index],
+			match commit_path.commit.is_zero() {
+				true => "the working tree".to_owned(),
+				false => format!("{:.*}", app.line_format.abbrev, commit_path.commit),
+			},
+			elapsed.as_secs()
+		);
+		Span::styled(status, Style::default().fg(Color::DarkGray))
+	});
+	if let Some(status) = loading_status.clone().filter(|_| app.blame.is_empty()) {
+		let paragraph = Paragraph::new(status).block(Block::default().title(title));
+		frame.render_widget(paragraph, chunks[0]);
+	} else if app.blame.is_empty() {
+		let text = match git::file_exists(app.repo, commit_path.commit, &commit_path.path) {
+			Ok(false) => format!(
+				"{} did not exist at {:.*}, press B to go back",
+				commit_path.path.display(),
+				app.line_format.abbrev,
+				commit_path.commit
+			),
+			_ => "no blame data".to_owned(),
+		};
+		let paragraph = Paragraph::new(Span::styled(text, Style::default().fg(app.theme.gutter)))
+			.block(Block::default().title(title));
+		frame.render_widget(paragraph, chunks[0]);
+	} else {
+		// what git has found so far, with the spinner in the title
+		if let Some(status) = loading_status {
+			title.spans.push(Span::raw(" "));
+			title.spans.push(status);
+		}
+		let list = List::new(items)
+			.block(Block::default().title(title))
+			.highlight_style(app.theme.selection_style(main_focused));
+		if app.folded {
+			app.fold_state.select(row_of(&rows, app.blame_state.selected()));
+		}
+		let state = match app.folded {
+			true => &mut app.fold_state,
+			false => &mut app.blame_state,
+		};
+		if let (true, Some(selected)) = (app.center, state.selected()) {
+			let height = usize::from(chunks[0].height.saturating_sub(1)); // minus the title
+			*state.offset_mut() = centered_offset(&app.item_heights, selected, height);
+		}
+		frame.render_stateful_widget(list, app.blame_area, state);
+
+		if app.minimap_area.height > 1 {
+			let height = usize::from(app.minimap_area.height - 1); // minus the title
+			let offset = state.offset();
+			let last = last_visible(&app.item_heights, offset, height);
+			let visible = match app.folded {
+				true => (rows[offset].first(), rows[last].last()),
+				false => (offset, last),
+			};
+			let cells = minimap(&app.blame, height, visible);
+			let mut area = app.minimap_area;
+			area.y += 1;
+			area.height -= 1;
+			frame.render_widget(
+				Paragraph::new(cells.into_iter().map(Line::from).collect::<Vec<_>>()),
+				area,
+			);
+		}
+	}
+
+	if let Some(panel) = &app.right_panel {
+		let mut text = panel.text.clone();
+		if let Some(history) = &app.history {
+			let elapsed = history.started.elapsed();
+			let frame_index = (elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize % SPINNER.len();
+			let status = match text.lines.is_empty() {
+				true => format!(
+					"{} following history… {}s, q to cancel",
+					SPINNER[frame_index],
+					elapsed.as_secs()
+				),
+				false => format!("{} loading more… q to stop", SPINNER[frame_index]),
+			};
+			text.lines
+				.push(Line::styled(status, Style::default().fg(app.theme.gutter)));
+		} else if panel.kind == PanelKind::LineHistory && text.lines.iter().all(|line| line.width() == 0) {
+			// git log -L succeeded without printing anything, e.g. for lines that aren't committed yet
+			text = Text::styled(
+				"no further history for this line",
+				Style::default().fg(app.theme.gutter),
+			);
+		}
+		if let Some(search) = &app.search {
+			for line in &mut text.lines {
+				if search.matches_line(line) {
+					for span in &mut line.spans {
+						span.patch_style(Style::default().bg(app.theme.search_match));
+					}
+				}
+			}
+		}
+		let mut area = chunks[1];
+		if let Some(pinned) = &panel.pinned {
+			let height = PINNED_HEIGHT.min(area.height);
+			let header =
+				Paragraph::new(pinned.clone()).block(Block::default().borders(Borders::LEFT | Borders::BOTTOM));
+			frame.render_widget(header, Rect { height, ..area });
+			(area.y, area.height) = (area.y + height, area.height - height);
+		}
+		let paragraph = Paragraph::new(text)
 			.block(Block::default().borders(Borders::LEFT))
 			.scroll((app.line_history_scroll, 0));
-		frame.render_widget(paragraph, chunks[1]);
+		frame.render_widget(paragraph, area);
+	} else if let Some(compare) = &mut app.compare {
+		let mut title = vec![Span::styled(
+			format!("{:.*}", app.line_format.abbrev, compare.commit),
+			Style::default().fg(app.theme.title).add_modifier(Modifier::BOLD),
+		)];
+		if let Some(base) = compare.merge_base {
+			title.push(Span::styled(
+				format!(" merge base {:.*}", app.line_format.abbrev, base),
+				Style::default().fg(Color::Gray),
+			));
+		}
+		if compare.loading.is_some() {
+			title.push(Span::styled(" blaming…", Style::default().fg(Color::DarkGray)));
+		} else if !compare.focused {
+			title.push(Span::styled(" <tab> to focus", Style::default().fg(app.theme.gutter)));
+		}
+		let items: Vec<ListItem> = compare
+			.blame
+			.iter()
+			.map(|line| ListItem::new(line.to_line(&line_format)))
+			.collect();
+		let list = List::new(items)
+			.block(Block::default().borders(Borders::LEFT).title(Line::from(title)))
+			.highlight_style(app.theme.selection_style(compare.focused));
+		frame.render_stateful_widget(list, chunks[1], &mut compare.state);
 	}
 
-	let command = match &app.search {
-		Some(search) => Some(format!("/{}", search.query.as_str())),
-		None => app.line_number.as_ref().map(|ln| format!(":{}", ln)),
+	let command = match (&app.search, &app.line_number, &app.message) {
+		_ if app.author_filter.as_ref().is_some_and(|filter| filter.editing) => {
+			Some(format!("author: {}", app.author_filter.as_ref().unwrap().query))
+		}
+		_ if app.rev_prompt.is_some() => {
+			let prompt = app.rev_prompt.as_ref().unwrap();
+			let label = if prompt.compare { "compare with" } else { "blame at" };
+			Some(format!("{}: {}", label, prompt.query))
+		}
+		_ if app.pending_mark == Some(Action::SetMark) => Some("mark: ".to_owned()),
+		_ if app.pending_mark == Some(Action::JumpToMark) => Some("jump to mark: ".to_owned()),
+		(Some(search), _, _) if search.editing => match search.invalid() {
+			Some(e) => Some(format!("/{}  (invalid regex: {})", search.query, e)),
+			None => Some(format!("/{}", search.query)),
+		},
+		(_, Some(ln), _) => Some(format!(":{}", ln)),
+		(_, _, Some(message)) => Some(message.clone()),
+		(Some(search), _, _) => Some(format!("/{}", search.query.as_str())),
+		_ => None,
 	};
-	if let Some(cmd_str) = command {
-		let paragraph = Paragraph::new(cmd_str).wrap(Wrap { trim: false });
-		let size = Rect::new(
-			frame.size().x,
-			frame.size().y + frame.size().height - 1,
-			frame.size().width,
-			1,
-		);
+	let status = match command {
+		Some(cmd_str) => Some(Line::from(cmd_str)),
+		None if app.heatmap => {
+			let mut legend = vec![Span::raw("age: old ")];
+			legend.extend(
+				app.theme
+					.age
+					.iter()
+					.map(|&c| Span::styled("  ", Style::default().bg(c))),
+			);
+			legend.push(Span::raw(" new"));
+			Some(Line::from(legend))
+		}
+		// who last touched the selected line, without opening the details panel
+		None => app.blame_state.selected().and_then(|i| app.blame.get(i)).map(|line| {
+			let date = match app.line_format.absolute_dates {
+				true => chrono::DateTime::<chrono::Local>::from(line.commit_time).to_rfc3339(),
+				false => git::fmt_age(line.commit_time),
+			};
+			Line::from(vec![
+				Span::raw(line.author.clone()),
+				Span::styled(format!(" {}", date), Style::default().fg(app.theme.gutter)),
+			])
+		}),
+	};
+	let size = Rect::new(
+		frame.size().x,
+		frame.size().y + frame.size().height - 1,
+		frame.size().width,
+		1,
+	);
+	let status_width = status.as_ref().map_or(0, |status| status.width());
+	if let Some(status) = status {
+		let paragraph = Paragraph::new(status).wrap(Wrap { trim: false });
 		frame.render_widget(paragraph, size);
 	}
+	let position = position_text(app, usize::from(size.width).saturating_sub(status_width + 1));
+	frame.render_widget(
+		Paragraph::new(Span::styled(position, Style::default().fg(app.theme.gutter))).alignment(Alignment::Right),
+		size,
+	);
+
+	if let Some(picker) = &mut app.file_picker {
+		let matches = picker.matches();
+		let title = format!("blame file: {}", picker.query);
+		let items: Vec<ListItem> = matches.iter().map(|file| ListItem::new(file.to_string())).collect();
+		let list = List::new(items)
+			.block(Block::default().borders(Borders::all()).title(title))
+			.highlight_style(Style::default().bg(app.theme.selection));
+		let area = centered_rect(80, 80, frame.size());
+		frame.render_widget(Clear, area);
+		frame.render_stateful_widget(list, area, &mut picker.state);
+	}
+
+	if let Some(picker) = &mut app.stack_picker {
+		let items: Vec<ListItem> = picker
+			.entries
+			.iter()
+			.map(|entry| ListItem::new(entry.clone()))
+			.collect();
+		let list = List::new(items)
+			.block(
+				Block::default()
+					.borders(Borders::all())
+					.title("blame stack: <enter> to go back to a blame"),
+			)
+			.highlight_style(Style::default().bg(app.theme.selection));
+		let area = centered_rect(80, 80, frame.size());
+		frame.render_widget(Clear, area);
+		frame.render_stateful_widget(list, area, &mut picker.state);
+	}
+
+	if let Some(picker) = &mut app.log_picker {
+		let items: Vec<ListItem> = picker
+			.entries
+			.iter()
+			.map(|entry| ListItem::new(entry.clone()))
+			.collect();
+		let title = match picker.loading {
+			Some(_) => {
+				let elapsed = picker.started.elapsed();
+				let frame_index = (elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize % SPINNER.len();
+				format!("{} loading file history… {}s", SPINNER[frame_index], elapsed.as_secs())
+			}
+			None => "file history: <enter> to blame at a commit".to_owned(),
+		};
+		let list = List::new(items)
+			.block(Block::default().borders(Borders::all()).title(title))
+			.highlight_style(Style::default().bg(app.theme.selection));
+		let area = centered_rect(80, 80, frame.size());
+		frame.render_widget(Clear, area);
+		frame.render_stateful_widget(list, area, &mut picker.state);
+	}
 
 	if let Some(popup) = &app.popup {
 		let paragraph = Paragraph::new(popup.clone()).wrap(Wrap { trim: false });
@@ -437,6 +2683,72 @@ fn ui(frame: &mut Frame, app: &mut App) {
 	}
 }
 
+// the offset that puts the selected row in the middle, without leaving blank rows at the bottom
+fn centered_offset(heights: &[usize], selected: usize, height: usize) -> usize {
+	let room = height.saturating_sub(heights.get(selected).copied().unwrap_or(1)) / 2;
+	let (mut offset, mut above) = (selected, 0);
+	while offset > 0 && above + heights[offset - 1] <= room {
+		offset -= 1;
+		above += heights[offset];
+	}
+	offset.min(max_offset(heights, height))
+}
+
+// the largest offset that still fills the screen
+fn max_offset(heights: &[usize], height: usize) -> usize {
+	let (mut offset, mut below) = (heights.len(), 0);
+	while offset > 0 && below + heights[offset - 1] <= height {
+		offset -= 1;
+		below += heights[offset];
+	}
+	offset
+}
+
+// where we are, for the right side of the status bar, dropping the least important parts to fit in width
+fn position_text(app: &App, width: usize) -> String {
+	let mut parts = vec![];
+	let last_line = app.blame.last().map_or(0, |line| line.line_num);
+	match app.blame_state.selected().and_then(|i| app.blame.get(i)) {
+		Some(line) => parts.push(format!("{}/{}", line.line_num, last_line)),
+		None => parts.push(format!("{} lines", app.blame.len())),
+	}
+	match app.commit_stack.last().unwrap().commit {
+		commit if commit.is_zero() => parts.push("worktree".to_owned()),
+		commit => parts.push(format!("{:.*}", app.line_format.abbrev, commit)),
+	}
+	if app.commit_stack.len() > 1 {
+		parts.push(format!("depth {}", app.commit_stack.len() - 1));
+	}
+	if app.active_author_filter().is_some() {
+		parts.push("[filtered]".to_owned());
+	}
+	if app.folded {
+		parts.push("[folded]".to_owned());
+	}
+	if let Some(layout) = app.layout.and_then(|i| app.layouts.get(i)) {
+		parts.push(format!("[{}]", layout.name));
+	}
+	while !parts.is_empty() {
+		let text = parts.join("  ");
+		if text.width() <= width {
+			return text;
+		}
+		parts.pop();
+	}
+	String::new()
+}
+
+// one of colors, which go from oldest to newest
+fn heatmap_color(colors: &[Color], time: SystemTime, oldest: SystemTime, newest: SystemTime) -> Color {
+	let span = newest.duration_since(oldest).unwrap_or_default().as_secs_f64();
+	let age = time.duration_since(oldest).unwrap_or_default().as_secs_f64();
+	if span == 0.0 {
+		return colors[colors.len() - 1];
+	}
+	let bucket = (age / span * colors.len() as f64) as usize;
+	colors[bucket.min(colors.len() - 1)]
+}
+
 // from https://github.com/tui-rs-revival/ratatui/blob/main/examples/popup.rs
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 	let popup_layout = Layout::default()
@@ -463,3 +2775,315 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 		)
 		.split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::HashSet, path::Path, time::SystemTime};
+
+	use git2::Oid;
+	use tui::widgets::ListState;
+
+	use super::{
+		author_boundary, centered_offset, clamp_selection, commit_boundary, fold_rows, fuzzy_match, handle_search,
+		last_visible, max_offset, minimap, move_selection, wrap_selection, App, CommitPath, ErrorLog, Marks, Row,
+		Search, ERROR_LOG_SIZE,
+	};
+	use crate::git::BlameHunk;
+	use git_whence::highlight::CodeSpan;
+
+	fn make_blame(lines: &[&'static str]) -> Vec<BlameHunk> {
+		lines
+			.iter()
+			.enumerate()
+			.map(|(i, line)| BlameHunk {
+				commit: Oid::zero(),
+				commit_time: std::time::SystemTime::UNIX_EPOCH,
+				author: String::new(),
+				author_email: String::new(),
+				summary: String::new(),
+				path: None,
+				line_num: i as i32 + 1,
+				first_in_hunk: false,
+				code: vec![CodeSpan::plain(*line)],
+				encoding: None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn fuzzy() {
+		assert!(fuzzy_match("gbp", "src/git_blame_porcelain.rs"));
+		assert!(fuzzy_match("", "readme.md"));
+		assert!(!fuzzy_match("pbg", "src/git_blame_porcelain.rs"));
+		assert!(fuzzy_match("Cargo", "Cargo.toml"));
+		assert!(!fuzzy_match("CARGO", "Cargo.toml"));
+	}
+
+	#[test]
+	fn minimap_cells() {
+		let mut blame = make_blame(&["a"; 8]);
+		for line in &mut blame[4..] {
+			line.commit = Oid::from_str("1234").unwrap();
+		}
+		let symbols = |height, visible| -> String {
+			minimap(&blame, height, visible)
+				.iter()
+				.map(|span| span.content.as_ref())
+				.collect()
+		};
+		assert_eq!(symbols(4, (0, 1)), "┃ ─ ");
+		// the second row covers lines 4 through 7
+		assert_eq!(symbols(2, (6, 7)), " ╂");
+		// taller than the file: only the first row of line 4 marks the change
+		assert_eq!(symbols(16, (7, 7)), "        ─     ┃┃");
+		assert_eq!(symbols(10, (0, 7)).chars().count(), 10);
+	}
+
+	#[test]
+	fn marks() {
+		let commit_path = |path: &str| CommitPath {
+			commit: Oid::zero(),
+			path: path.into(),
+			view: ListState::default(),
+		};
+		let mut marks = Marks::default();
+		marks.for_blame(&commit_path("a.rs")).insert('a', 3);
+		assert_eq!(marks.for_blame(&commit_path("a.rs")).get(&'a'), Some(&3));
+		// cleared by blaming another file
+		assert!(marks.for_blame(&commit_path("b.rs")).is_empty());
+		assert!(marks.for_blame(&commit_path("a.rs")).is_empty());
+	}
+
+	#[test]
+	fn boundaries() {
+		let mut blame = make_blame(&["a"; 6]);
+		for line in &mut blame[2..4] {
+			line.commit = Oid::from_str("1234").unwrap();
+		}
+		assert_eq!(commit_boundary(&blame, 0, true), Some(2));
+		assert_eq!(commit_boundary(&blame, 2, true), Some(4));
+		assert_eq!(commit_boundary(&blame, 4, true), None);
+		assert_eq!(commit_boundary(&blame, 3, false), Some(2));
+		assert_eq!(commit_boundary(&blame, 2, false), Some(0));
+		assert_eq!(commit_boundary(&blame, 0, false), None);
+
+		// a run by one author across commits
+		for line in &mut blame[3..] {
+			line.author_email = "b@example.com".to_owned();
+		}
+		assert_eq!(author_boundary(&blame, 0, true), Some(3));
+		assert_eq!(author_boundary(&blame, 3, true), None);
+		assert_eq!(author_boundary(&blame, 5, false), Some(3));
+		assert_eq!(author_boundary(&blame, 3, false), Some(0));
+	}
+
+	#[test]
+	fn centered() {
+		let heights = [1; 100];
+		assert_eq!(centered_offset(&heights, 50, 11), 45);
+		assert_eq!(centered_offset(&heights, 2, 11), 0);
+		assert_eq!(centered_offset(&heights, 98, 11), 89);
+		// a wrapped line above only counts if all of it fits
+		assert_eq!(centered_offset(&[1, 3, 1, 1, 1], 2, 4), 2);
+		assert_eq!(centered_offset(&[3, 1, 1, 1, 1, 1, 1, 1], 4, 7), 1);
+	}
+
+	#[test]
+	fn viewport() {
+		let heights = [1, 1, 2, 1, 1];
+		assert_eq!(max_offset(&heights, 3), 3);
+		assert_eq!(max_offset(&heights, 10), 0);
+		assert_eq!(last_visible(&heights, 0, 3), 1); // the wrapped row doesn't fit
+		assert_eq!(last_visible(&heights, 1, 3), 2);
+		assert_eq!(last_visible(&heights, 3, 3), 4);
+		assert_eq!(last_visible(&[], 0, 3), 0);
+	}
+
+	#[test]
+	fn folds() {
+		let mut blame = make_blame(&["a", "b", "c", "d", "e", "f"]);
+		let commits = [1, 1, 2, 3, 3, 3];
+		for (line, commit) in blame.iter_mut().zip(commits) {
+			line.commit = Oid::from_str(&commit.to_string()).unwrap();
+		}
+		assert_eq!(
+			fold_rows(&blame, &HashSet::new()),
+			[
+				Row::Fold { start: 0, len: 2 },
+				Row::Line(2),
+				Row::Fold { start: 3, len: 3 }
+			]
+		);
+		assert_eq!(
+			fold_rows(&blame, &HashSet::from([3])),
+			[
+				Row::Fold { start: 0, len: 2 },
+				Row::Line(2),
+				Row::Line(3),
+				Row::Line(4),
+				Row::Line(5)
+			]
+		);
+	}
+
+	#[test]
+	fn search_wraps() {
+		let blame = make_blame(&["Foo", "bar", "foo", "baz"]);
+		let search = Search {
+			editing: false,
+			query: "foo".to_owned(),
+			..Default::default()
+		};
+		let mut state = ListState::default();
+		handle_search(&blame, &search, &mut state, true);
+		assert_eq!(state.selected(), Some(0));
+		handle_search(&blame, &search, &mut state, true);
+		assert_eq!(state.selected(), Some(2));
+		handle_search(&blame, &search, &mut state, true);
+		assert_eq!(state.selected(), Some(0));
+		handle_search(&blame, &search, &mut state, false);
+		assert_eq!(state.selected(), Some(2));
+
+		let search = Search {
+			editing: false,
+			query: "Foo".to_owned(),
+			..Default::default()
+		};
+		handle_search(&blame, &search, &mut state, true);
+		assert_eq!(state.selected(), Some(0));
+		handle_search(&blame, &search, &mut state, true);
+		assert_eq!(state.selected(), Some(0));
+	}
+
+	#[test]
+	fn regex_search() {
+		let blame = make_blame(&["foo_bar", "Foo", "foobar", "baz"]);
+		let mut search = Search {
+			query: "foo_?bar$".to_owned(),
+			..Default::default()
+		};
+		search.compile(true);
+		let matching: Vec<_> = (0..blame.len()).filter(|&i| search.matches(&blame[i])).collect();
+		assert_eq!(matching, [0, 2]);
+
+		search.query = "\\Vfoo_?bar$".to_owned();
+		search.compile(true);
+		assert!(!blame.iter().any(|hunk| search.matches(hunk)));
+
+		search.query = "\\v^F".to_owned();
+		search.compile(false);
+		let matching: Vec<_> = (0..blame.len()).filter(|&i| search.matches(&blame[i])).collect();
+		assert_eq!(matching, [1]); // the code on its own, without the sha before it
+		search.query = "\\vFoo$".to_owned();
+		search.compile(false);
+		let matching: Vec<_> = (0..blame.len()).filter(|&i| search.matches(&blame[i])).collect();
+		assert_eq!(matching, [1]);
+
+		search.query = "\\v(foo".to_owned();
+		search.compile(false);
+		assert_eq!(search.invalid(), Some("unclosed group"));
+		assert!(!blame.iter().any(|hunk| search.matches(hunk)));
+	}
+
+	#[test]
+	fn move_selection_empty() {
+		let mut state = ListState::default();
+		move_selection(&mut state, 0, 1);
+		assert_eq!(state.selected(), None);
+		move_selection(&mut state, 0, -1);
+		assert_eq!(state.selected(), None);
+
+		move_selection(&mut state, 3, 10);
+		assert_eq!(state.selected(), Some(0));
+		move_selection(&mut state, 3, 10);
+		assert_eq!(state.selected(), Some(2));
+		move_selection(&mut state, 3, -10);
+		assert_eq!(state.selected(), Some(0));
+	}
+
+	#[test]
+	fn error_log() {
+		let mut log = ErrorLog::default();
+		assert_eq!(log.text().lines.len(), 1); // no errors
+		for i in 0..ERROR_LOG_SIZE + 5 {
+			log.push(SystemTime::UNIX_EPOCH, format!("error {}", i));
+		}
+		assert_eq!(log.entries.len(), ERROR_LOG_SIZE);
+		assert_eq!(log.entries[0].1, "error 5");
+		assert_eq!(log.text().lines.len(), ERROR_LOG_SIZE);
+	}
+
+	#[test]
+	fn clamp_after_reblame() {
+		assert_eq!(clamp_selection(Some(5), 10), Some(5));
+		assert_eq!(clamp_selection(Some(10), 10), Some(9));
+		assert_eq!(clamp_selection(Some(15), 10), Some(9));
+		assert_eq!(clamp_selection(Some(0), 0), None);
+		assert_eq!(clamp_selection(None, 10), None);
+	}
+
+	#[test]
+	fn wrap_scroll() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let mut app = App::new(&repo, Path::new("src/main.rs"), Oid::zero());
+		app.blame = make_blame(&["a", "b", "c"]);
+		app.blame_state.select(Some(2));
+		assert!(!wrap_selection(&mut app, true));
+		app.wrap_scroll = true;
+		assert!(wrap_selection(&mut app, true));
+		assert_eq!(app.blame_state.selected(), Some(0));
+		assert!(!wrap_selection(&mut app, true));
+		assert!(wrap_selection(&mut app, false));
+		assert_eq!(app.blame_state.selected(), Some(2));
+	}
+
+	#[test]
+	fn pinned_line() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let mut app = App::new(&repo, Path::new("src/main.rs"), Oid::zero());
+		app.blame = make_blame(&["fn main() {", "}"]);
+		let text = |line: tui::text::Line| {
+			line.spans
+				.iter()
+				.map(|span| span.content.to_string())
+				.collect::<String>()
+		};
+		assert_eq!(text(app.pinned_line(0, 0)), "0000000 1 fn main() {");
+		assert_eq!(text(app.pinned_line(0, 1)), "0000000 1-2 fn main() {");
+	}
+
+	#[test]
+	fn blame_stack() {
+		let repo = git2::Repository::open_from_env().unwrap();
+		let path = Path::new("src/main.rs");
+		let head = repo.head().unwrap().peel_to_commit().unwrap();
+		let mut app = App::new(&repo, path, head.id());
+		let grandparent = head.parent(0).unwrap().parent_id(0).unwrap();
+		for parent in [head.parent_id(0).unwrap(), grandparent] {
+			app.commit_stack.push(CommitPath {
+				commit: parent,
+				path: path.to_owned(),
+				view: ListState::default(),
+			});
+		}
+		app.reblame().unwrap();
+		assert_eq!(app.stack_entries().len(), 3);
+		app.pop_blame_to(1).unwrap();
+		assert_eq!(app.commit_stack.len(), 1);
+		assert!(!app.blame.is_empty());
+		// the blame left is kept for going back to
+		assert!(app.blame_cache.contains(&app.blame_key(grandparent, path)));
+
+		// a blame that fails underneath leaves the stack alone
+		app.commit_stack[0].path = Path::new("no such file").to_owned();
+		app.commit_stack.push(CommitPath {
+			commit: grandparent,
+			path: path.to_owned(),
+			view: ListState::default(),
+		});
+		app.reblame().unwrap();
+		assert!(app.pop_blame_to(1).is_err());
+		assert_eq!(app.commit_stack.len(), 2);
+		assert!(!app.blame.is_empty());
+	}
+}
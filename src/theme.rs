@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use tui::style::{Color, Modifier, Style};
+
+use crate::{config::ThemeConfig, render::DiffColors};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+	pub title: Color,              // the commit in the title
+	pub selection: Color,          // background of the selected line
+	pub inactive_selection: Color, // likewise while another pane has focus, when it's also underlined
+	pub gutter: Color,             // line numbers and other dimmed text
+	pub search_match: Color,       // background of lines matching the search
+	pub age: Vec<Color>,           // heatmap backgrounds from oldest to newest
+	pub diff: DiffColors,          // commit and file diffs in the right panel
+}
+
+impl Theme {
+	pub fn dark() -> Theme {
+		Theme {
+			title: Color::Cyan,
+			selection: Color::Indexed(238), // 232 is black, 255 is white; 238 is dark gray
+			inactive_selection: Color::Indexed(236),
+			gutter: Color::DarkGray,
+			search_match: Color::Indexed(58),
+			// dark enough to keep the code readable
+			age: [17, 18, 54, 90, 89, 88].map(Color::Indexed).to_vec(),
+			diff: DiffColors::default(),
+		}
+	}
+
+	pub fn light() -> Theme {
+		Theme {
+			title: Color::Blue,
+			selection: Color::Indexed(251),
+			inactive_selection: Color::Indexed(254),
+			gutter: Color::Indexed(245),
+			search_match: Color::Indexed(229),
+			age: [195, 189, 225, 224, 223, 217].map(Color::Indexed).to_vec(),
+			// cyan is hard to read on white
+			diff: DiffColors {
+				file_header: Color::Blue,
+				hunk_header: Color::Magenta,
+				..DiffColors::default()
+			},
+		}
+	}
+
+	fn named(name: &str) -> Option<Theme> {
+		match name {
+			"dark" => Some(Theme::dark()),
+			"light" => Some(Theme::light()),
+			_ => None,
+		}
+	}
+
+	// starts from the named built-in theme and replaces whichever colors are set
+	pub fn with_overrides(config: &ThemeConfig) -> Result<Theme, String> {
+		let mut theme = match &config.name {
+			Some(name) => {
+				Theme::named(name).ok_or_else(|| format!("unknown theme {} (expected dark or light)", name))?
+			}
+			None => Theme::dark(),
+		};
+		for (color, name) in [
+			(&mut theme.title, &config.title),
+			(&mut theme.selection, &config.selection),
+			(&mut theme.inactive_selection, &config.inactive_selection),
+			(&mut theme.gutter, &config.gutter),
+			(&mut theme.search_match, &config.search_match),
+			(&mut theme.diff.file_header, &config.diff_file),
+			(&mut theme.diff.hunk_header, &config.diff_hunk),
+			(&mut theme.diff.added, &config.diff_added),
+			(&mut theme.diff.removed, &config.diff_removed),
+		] {
+			if let Some(name) = name {
+				*color = parse_color(name)?;
+			}
+		}
+		if let Some(age) = &config.age {
+			if age.is_empty() {
+				return Err("theme age needs at least one color".to_owned());
+			}
+			theme.age = age.iter().map(|name| parse_color(name)).collect::<Result<_, _>>()?;
+		}
+		Ok(theme)
+	}
+
+	// the selected line of a list, still marked when the list doesn't have focus
+	pub fn selection_style(&self, focused: bool) -> Style {
+		match focused {
+			true => Style::default().bg(self.selection),
+			false => Style::default()
+				.bg(self.inactive_selection)
+				.add_modifier(Modifier::UNDERLINED),
+		}
+	}
+}
+
+impl Default for Theme {
+	fn default() -> Theme {
+		Theme::dark()
+	}
+}
+
+// a name like "cyan" or "light-blue", an index into the 256-color palette, or #rrggbb
+fn parse_color(name: &str) -> Result<Color, String> {
+	Color::from_str(name).map_err(|_| format!("unknown color {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+	use tui::style::Color;
+
+	use super::Theme;
+	use crate::config::ThemeConfig;
+
+	#[test]
+	fn overrides() {
+		assert_eq!(Theme::with_overrides(&ThemeConfig::default()).unwrap(), Theme::dark());
+
+		let config: ThemeConfig = toml::from_str(
+			r##"
+name = "light"
+selection = "#d0d0d0"
+gutter = "dark-gray"
+age = ["17", "red"]
+diff_added = "light-green"
+"##,
+		)
+		.unwrap();
+		let theme = Theme::with_overrides(&config).unwrap();
+		assert_eq!(theme.title, Theme::light().title);
+		assert_eq!(theme.selection, Color::Rgb(0xd0, 0xd0, 0xd0));
+		assert_eq!(theme.gutter, Color::DarkGray);
+		assert_eq!(theme.age, [Color::Indexed(17), Color::Red]);
+		assert_eq!(theme.diff.added, Color::LightGreen);
+		assert_eq!(theme.diff.removed, Theme::light().diff.removed);
+
+		let config: ThemeConfig = toml::from_str(r#"title = "chartreuse""#).unwrap();
+		assert!(Theme::with_overrides(&config).is_err());
+		let config: ThemeConfig = toml::from_str(r#"name = "solarized""#).unwrap();
+		assert!(Theme::with_overrides(&config).is_err());
+	}
+}